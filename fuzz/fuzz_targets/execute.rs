@@ -0,0 +1,61 @@
+// a register and memory state, then opcodes run against it one at a time.
+// the input is a count of opcodes, the opcodes, then bytes laid over a
+// fresh cpu's save state, so short inputs set the registers and the start
+// of ram and leave the rest as it was.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use chippy8::cpu::Cpu;
+use chippy8::quirks::Quirks;
+
+// the save state's magic number and version
+const STATE_HEADER: usize = 5;
+
+fn check(cpu: &Cpu) {
+    assert!(cpu.pc() < 0x1000, "pc {:X}", cpu.pc());
+    assert!(cpu.i() < 0x1000, "i {:X}", cpu.i());
+    assert!(cpu.sp() < 16, "sp {}", cpu.sp());
+    let state = cpu.save_state();
+    let mut restored = Cpu::new();
+    restored.load_state(&state).expect("a saved state loads");
+    assert_eq!(restored.save_state(), state);
+}
+
+fn quirks(bits: u8) -> Quirks {
+    Quirks {
+        shift: bits & 0x01 != 0,
+        memory_increment_by_x: bits & 0x02 != 0,
+        memory_leave_i_unchanged: bits & 0x04 != 0,
+        wrap: bits & 0x08 != 0,
+        jump: bits & 0x10 != 0,
+        vblank: bits & 0x20 != 0,
+        logic: bits & 0x40 != 0,
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    let (&count, rest) = match data.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+    let (&quirk_bits, rest) = match rest.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+    let opcode_bytes = (count as usize * 2).min(rest.len());
+    let (opcodes, overlay) = rest.split_at(opcode_bytes);
+    let mut cpu = Cpu::new();
+    cpu.set_seed(0);
+    cpu.set_quirks(quirks(quirk_bits));
+    let mut state = cpu.save_state();
+    let length = overlay.len().min(state.len() - STATE_HEADER);
+    state[STATE_HEADER..STATE_HEADER + length].copy_from_slice(&overlay[..length]);
+    // states that don't make sense are refused, which is what's wanted
+    if cpu.load_state(&state).is_err() {
+        return;
+    }
+    check(&cpu);
+    for opcode in opcodes.chunks_exact(2) {
+        cpu.execute(u16::from_be_bytes([opcode[0], opcode[1]]));
+        check(&cpu);
+    }
+});
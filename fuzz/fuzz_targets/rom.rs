@@ -0,0 +1,45 @@
+// arbitrary bytes as a rom: analysed, disassembled and run for a few frames
+// with keys going up and down
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use chippy8::analyser;
+use chippy8::cartridge::Cartridge;
+use chippy8::cpu::{Cpu, RESET_VECTOR};
+use chippy8::disasm;
+
+const FRAMES: u32 = 20;
+const INSTRUCTIONS_PER_FRAME: u32 = 200;
+
+fn check(cpu: &Cpu) {
+    assert!(cpu.pc() < 0x1000, "pc {:X}", cpu.pc());
+    assert!(cpu.i() < 0x1000, "i {:X}", cpu.i());
+    assert!(cpu.sp() < 16, "sp {}", cpu.sp());
+    let state = cpu.save_state();
+    let mut restored = Cpu::new();
+    restored.load_state(&state).expect("a saved state loads");
+    assert_eq!(restored.save_state(), state);
+}
+
+fuzz_target!(|data: &[u8]| {
+    let cart = match Cartridge::from_bytes(data.to_vec()) {
+        Ok(cart) => cart,
+        Err(_) => return,
+    };
+    analyser::analyse(&cart.rom);
+    disasm::listing(&cart.rom, RESET_VECTOR);
+    let mut cpu = Cpu::new();
+    if cpu.load_rom(&cart.rom).is_err() {
+        return;
+    }
+    cpu.set_seed(0);
+    cpu.set_instructions_per_frame(INSTRUCTIONS_PER_FRAME);
+    for frame in 0..FRAMES {
+        // a different key every other frame, so LD Vx, K gets its key
+        let mut keys = [false; 16];
+        if frame % 2 == 1 {
+            keys[(frame / 2) as usize % 16] = true;
+        }
+        cpu.tick_60_hz(&keys);
+        check(&cpu);
+    }
+});
@@ -0,0 +1,261 @@
+use std::collections::{BTreeMap, BTreeSet};
+use crate::cartridge::Platform;
+use crate::cpu::RESET_VECTOR;
+use crate::quirks::Quirks;
+
+// how many addresses a finding lists before it just gives a count
+const MAX_ADDRESSES: usize = 6;
+
+// the oldest platform that has the instruction, or None for an opcode no
+// platform knows
+pub fn opcode_platform(opcode: u16) -> Option<Platform> {
+    let kk = opcode & 0x00FF;
+    let n = opcode & 0x000F;
+    Some(match opcode >> 12 {
+        // long i, planes, audio pattern
+        0xF if matches!(opcode, 0xF000 | 0xF001 | 0xF101 | 0xF201 | 0xF301 | 0xF002) => Platform::XoChip,
+        // scroll up
+        0x0 if opcode & 0xFFF0 == 0x00D0 => Platform::XoChip,
+        // save/load a range of registers
+        0x5 if n == 0x2 || n == 0x3 => Platform::XoChip,
+        // scroll, exit, lores/hires
+        0x0 if opcode & 0xFFF0 == 0x00C0 || (0x00FB..=0x00FF).contains(&opcode) => Platform::SuperChip,
+        0x0 => Platform::Chip8,
+        0x1 | 0x2 | 0x3 | 0x4 | 0x6 | 0x7 | 0xA | 0xB | 0xC => Platform::Chip8,
+        0x5 | 0x9 if n == 0 => Platform::Chip8,
+        0x8 if n <= 0x7 || n == 0xE => Platform::Chip8,
+        // 16x16 sprites
+        0xD if n == 0 => Platform::SuperChip,
+        0xD => Platform::Chip8,
+        0xE if kk == 0x9E || kk == 0xA1 => Platform::Chip8,
+        0xF => match kk {
+            0x07 | 0x0A | 0x15 | 0x18 | 0x1E | 0x29 | 0x33 | 0x55 | 0x65 => Platform::Chip8,
+            // big font, flags
+            0x30 | 0x75 | 0x85 => Platform::SuperChip,
+            // pitch
+            0x3A => Platform::XoChip,
+            _ => return None,
+        },
+        _ => return None,
+    })
+}
+
+// code whose behaviour depends on the quirks
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Pattern {
+    // 8xy6/8xyE with x != y: shifts Vy or Vx depending on the shift quirk
+    Shift,
+    // Fx55/Fx65 followed by an instruction that uses I: what I is then
+    // depends on the memory quirks
+    MemoryThenI,
+    // Bnnn: adds V0 or Vx depending on the jump quirk
+    Jump,
+}
+
+impl Pattern {
+    pub fn describe(self) -> &'static str {
+        match self {
+            Pattern::Shift => "8xy6/8xyE with x != y (shift quirk)",
+            Pattern::MemoryThenI => "Fx55/Fx65 then a use of I (memory quirks)",
+            Pattern::Jump => "Bnnn (jump quirk)",
+        }
+    }
+
+    // the quirks it depends on, by chip-8-database name
+    pub fn quirks(self) -> &'static [&'static str] {
+        match self {
+            Pattern::Shift => &["shift"],
+            Pattern::MemoryThenI => &["memoryIncrementByX", "memoryLeaveIUnchanged"],
+            Pattern::Jump => &["jump"],
+        }
+    }
+}
+
+pub struct Analysis {
+    // addresses of the instructions control flow reaches from the reset vector
+    pub reached: BTreeSet<u16>,
+    // rom bytes that nothing reaches: sprites and other data, or code only
+    // reached through Bnnn
+    pub unreached_bytes: usize,
+    // the newer platforms' instructions in reached code, with their addresses
+    pub platform_opcodes: BTreeMap<u16, Vec<u16>>,
+    pub unknown_opcodes: Vec<u16>,
+    pub patterns: BTreeMap<Pattern, Vec<u16>>,
+    pub platform: Platform,
+}
+
+// the instruction at addr, or None past the end of the rom
+fn fetch(rom: &[u8], addr: u16) -> Option<u16> {
+    let offset = addr.checked_sub(RESET_VECTOR)? as usize;
+    let bytes = rom.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+// XO-CHIP's long load is four bytes, and skips jump over all of it
+fn length(opcode: u16) -> u16 {
+    if opcode == 0xF000 { 4 } else { 2 }
+}
+
+fn is_skip(opcode: u16) -> bool {
+    let n = opcode & 0x000F;
+    let kk = opcode & 0x00FF;
+    match opcode >> 12 {
+        0x3 | 0x4 => true,
+        0x5 | 0x9 => n == 0,
+        0xE => kk == 0x9E || kk == 0xA1,
+        _ => false,
+    }
+}
+
+// where execution can go after the instruction at addr. Bnnn's target is
+// computed at run time, so only nnn itself is followed.
+fn successors(rom: &[u8], addr: u16, opcode: u16) -> Vec<u16> {
+    let next = addr.wrapping_add(length(opcode));
+    let nnn = opcode & 0x0FFF;
+    match opcode >> 12 {
+        _ if opcode == 0x00EE || opcode == 0x00FD => vec![],
+        0x1 => vec![nnn],
+        0x2 => vec![nnn, next],
+        0xB => vec![nnn],
+        _ if is_skip(opcode) => {
+            let skipped = fetch(rom, next).map(length).unwrap_or(2);
+            vec![next, next.wrapping_add(skipped)]
+        }
+        _ => vec![next],
+    }
+}
+
+fn sets_i(opcode: u16) -> bool {
+    let kk = opcode & 0x00FF;
+    opcode >> 12 == 0xA || opcode == 0xF000 || (opcode >> 12 == 0xF && (kk == 0x29 || kk == 0x30))
+}
+
+fn uses_i(opcode: u16) -> bool {
+    let kk = opcode & 0x00FF;
+    opcode >> 12 == 0xD || (opcode >> 12 == 0xF && matches!(kk, 0x1E | 0x33 | 0x55 | 0x65)) ||
+        (opcode >> 12 == 0x5 && matches!(opcode & 0x000F, 0x2 | 0x3))
+}
+
+fn is_memory_op(opcode: u16) -> bool {
+    opcode >> 12 == 0xF && matches!(opcode & 0x00FF, 0x55 | 0x65)
+}
+
+// whether some path from the Fx55/Fx65 at addr uses I before setting it
+fn uses_i_after(rom: &[u8], addr: u16, opcode: u16) -> bool {
+    let mut seen = BTreeSet::new();
+    let mut todo = successors(rom, addr, opcode);
+    while let Some(addr) = todo.pop() {
+        if !seen.insert(addr) {
+            continue;
+        }
+        let opcode = match fetch(rom, addr) {
+            Some(opcode) if opcode_platform(opcode).is_some() => opcode,
+            _ => continue,
+        };
+        if uses_i(opcode) {
+            return true;
+        }
+        if !sets_i(opcode) {
+            todo.extend(successors(rom, addr, opcode));
+        }
+    }
+    false
+}
+
+// follows every branch from the reset vector, so unlike the opcode scan
+// Cartridge does, sprite data isn't taken for instructions
+pub fn analyse(rom: &[u8]) -> Analysis {
+    let mut reached = BTreeSet::new();
+    let mut platform_opcodes: BTreeMap<u16, Vec<u16>> = BTreeMap::new();
+    let mut unknown_opcodes = Vec::new();
+    let mut patterns: BTreeMap<Pattern, Vec<u16>> = BTreeMap::new();
+    let mut platform = Platform::Chip8;
+    let mut todo = vec![RESET_VECTOR];
+    while let Some(addr) = todo.pop() {
+        if reached.contains(&addr) {
+            continue;
+        }
+        let opcode = match fetch(rom, addr) {
+            Some(opcode) => opcode,
+            None => continue,
+        };
+        reached.insert(addr);
+        match opcode_platform(opcode) {
+            None => {
+                unknown_opcodes.push(addr);
+                continue;
+            }
+            Some(Platform::Chip8) => {}
+            Some(newer) => {
+                platform = platform.max(newer);
+                platform_opcodes.entry(opcode).or_default().push(addr);
+            }
+        }
+        let x = (opcode & 0x0F00) >> 8;
+        let y = (opcode & 0x00F0) >> 4;
+        let n = opcode & 0x000F;
+        let pattern = match opcode >> 12 {
+            0x8 if (n == 0x6 || n == 0xE) && x != y => Some(Pattern::Shift),
+            0xB => Some(Pattern::Jump),
+            0xF if is_memory_op(opcode) && uses_i_after(rom, addr, opcode) => Some(Pattern::MemoryThenI),
+            _ => None,
+        };
+        if let Some(pattern) = pattern {
+            patterns.entry(pattern).or_default().push(addr);
+        }
+        todo.extend(successors(rom, addr, opcode));
+    }
+    let reached_bytes: usize = reached.iter()
+        .filter_map(|&addr| fetch(rom, addr))
+        .map(|opcode| length(opcode) as usize)
+        .sum();
+    for addrs in platform_opcodes.values_mut().chain(patterns.values_mut()) {
+        addrs.sort_unstable();
+    }
+    unknown_opcodes.sort_unstable();
+    Analysis {
+        reached,
+        unreached_bytes: rom.len().saturating_sub(reached_bytes),
+        platform_opcodes,
+        unknown_opcodes,
+        patterns,
+        platform,
+    }
+}
+
+// "204, 2F0, 31A and 3 more"
+pub fn addresses(addrs: &[u16]) -> String {
+    let text: Vec<String> = addrs.iter().take(MAX_ADDRESSES).map(|addr| format!("{:03X}", addr)).collect();
+    let mut text = text.join(", ");
+    if addrs.len() > MAX_ADDRESSES {
+        text.push_str(&format!(" and {} more", addrs.len() - MAX_ADDRESSES));
+    }
+    text
+}
+
+impl Analysis {
+    // the quirks preset to run the rom with, by the names --quirks takes
+    pub fn preset(&self) -> &'static str {
+        match self.platform {
+            Platform::XoChip => "xochip",
+            Platform::SuperChip => "schip",
+            Platform::Chip8 => "chip8",
+        }
+    }
+
+    // the quirks the rom's code was found to depend on, as the preset sets
+    // them. the code alone can't say which way a rom wants them, so that
+    // comes from the platform; the rest of the preset the analysis can't
+    // check either way.
+    pub fn quirks(&self) -> Vec<(&'static str, bool)> {
+        let preset = Quirks::preset(self.preset()).unwrap_or_default();
+        self.patterns.keys()
+            .flat_map(|pattern| pattern.quirks().iter())
+            .map(|&name| (name, preset.get(name).unwrap_or_default()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+#[path = "./analyser_tests.rs"]
+mod analyser_tests;
@@ -0,0 +1,129 @@
+use super::*;
+
+fn patterns(rom: &[u8]) -> Vec<(Pattern, Vec<u16>)> {
+    analyse(rom).patterns.into_iter().collect()
+}
+
+
+#[test]
+fn test_opcode_platform() {
+    assert_eq!(opcode_platform(0x00E0), Some(Platform::Chip8));
+    assert_eq!(opcode_platform(0x8126), Some(Platform::Chip8));
+    assert_eq!(opcode_platform(0x00FF), Some(Platform::SuperChip));
+    assert_eq!(opcode_platform(0xD120), Some(Platform::SuperChip));
+    assert_eq!(opcode_platform(0xF275), Some(Platform::SuperChip));
+    assert_eq!(opcode_platform(0xF000), Some(Platform::XoChip));
+    assert_eq!(opcode_platform(0x5122), Some(Platform::XoChip));
+    assert_eq!(opcode_platform(0x5121), None);
+    assert_eq!(opcode_platform(0x8128), None);
+    assert_eq!(opcode_platform(0xF2FF), None);
+}
+
+
+#[test]
+fn test_successors() {
+    let rom = [0x30, 0x00, 0xF0, 0x00, 0x12, 0x34, 0x00, 0xE0];
+    assert_eq!(successors(&rom, 0x300, 0x00EE), vec![]);
+    assert_eq!(successors(&rom, 0x300, 0x00FD), vec![]);
+    assert_eq!(successors(&rom, 0x300, 0x1234), vec![0x234]);
+    assert_eq!(successors(&rom, 0x300, 0x2234), vec![0x234, 0x302]);
+    assert_eq!(successors(&rom, 0x300, 0xB234), vec![0x234]);
+    assert_eq!(successors(&rom, 0x300, 0x00E0), vec![0x302]);
+    // a skip over the four byte long load
+    assert_eq!(successors(&rom, 0x200, 0x3000), vec![0x202, 0x206]);
+    assert_eq!(successors(&rom, 0x202, 0xF000), vec![0x206]);
+    // past the end of the rom a skip counts on two bytes
+    assert_eq!(successors(&rom, 0x206, 0x4000), vec![0x208, 0x20A]);
+}
+
+
+#[test]
+fn test_skip_long_load() {
+    // 200: SE V0, 0   202: LD I, long 1234   206: CLS
+    let analysis = analyse(&[0x30, 0x00, 0xF0, 0x00, 0x12, 0x34, 0x00, 0xE0]);
+    assert_eq!(analysis.reached.iter().copied().collect::<Vec<_>>(), vec![0x200, 0x202, 0x206]);
+    assert_eq!(analysis.unreached_bytes, 0);
+    assert_eq!(analysis.platform, Platform::XoChip);
+    assert_eq!(analysis.platform_opcodes.get(&0xF000), Some(&vec![0x202]));
+    assert_eq!(analysis.preset(), "xochip");
+}
+
+
+#[test]
+fn test_shift() {
+    // 200: SHR V1, V2   202: JP 202
+    let analysis = analyse(&[0x81, 0x26, 0x12, 0x02]);
+    assert_eq!(patterns(&[0x81, 0x26, 0x12, 0x02]), vec![(Pattern::Shift, vec![0x200])]);
+    assert_eq!(analysis.preset(), "chip8");
+    assert_eq!(analysis.quirks(), vec![("shift", false)]);
+    // shifting a register in place is the same either way
+    assert_eq!(patterns(&[0x81, 0x1E, 0x12, 0x02]), vec![]);
+}
+
+
+#[test]
+fn test_memory_then_i() {
+    // 200: LD I, 300   202: LD V2, [I]   204: DRW V0, V1, 5   206: JP 206
+    let rom = [0xA3, 0x00, 0xF2, 0x65, 0xD0, 0x15, 0x12, 0x06];
+    let analysis = analyse(&rom);
+    assert_eq!(patterns(&rom), vec![(Pattern::MemoryThenI, vec![0x202])]);
+    assert_eq!(analysis.quirks(), vec![("memoryIncrementByX", false), ("memoryLeaveIUnchanged", false)]);
+    // under SUPER-CHIP, with its 16x16 sprites, I is left unchanged
+    let rom = [0xA3, 0x00, 0xF2, 0x65, 0xD0, 0x10, 0x12, 0x06];
+    let analysis = analyse(&rom);
+    assert_eq!(analysis.preset(), "schip");
+    assert_eq!(analysis.quirks(), vec![("memoryIncrementByX", false), ("memoryLeaveIUnchanged", true)]);
+    // I set again before it's used
+    assert_eq!(patterns(&[0xA3, 0x00, 0xF2, 0x65, 0xA3, 0x00, 0xD0, 0x15]), vec![]);
+}
+
+
+#[test]
+fn test_uses_i_after() {
+    // used on one path, through a skip
+    let rom = [0xF2, 0x55, 0x30, 0x00, 0x12, 0x08, 0xF1, 0x1E, 0x00, 0xEE];
+    assert!(uses_i_after(&rom, 0x200, 0xF255));
+    // a subroutine that uses I
+    let rom = [0xF2, 0x55, 0x22, 0x06, 0x00, 0xEE, 0xF2, 0x33];
+    assert!(uses_i_after(&rom, 0x200, 0xF255));
+    // a loop that never does, and unknown opcodes stop the search
+    assert!(!uses_i_after(&[0xF2, 0x55, 0x12, 0x02], 0x200, 0xF255));
+    assert!(!uses_i_after(&[0xF2, 0x55, 0xF2, 0xFF, 0xD0, 0x15], 0x200, 0xF255));
+}
+
+
+#[test]
+fn test_jump() {
+    // 200: JP V0, 206   202: data   206: RET
+    let rom = [0xB2, 0x06, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0xEE];
+    let analysis = analyse(&rom);
+    assert_eq!(patterns(&rom), vec![(Pattern::Jump, vec![0x200])]);
+    // only nnn is followed, so the data isn't taken for an unknown opcode
+    assert_eq!(analysis.reached.iter().copied().collect::<Vec<_>>(), vec![0x200, 0x206]);
+    assert_eq!(analysis.unreached_bytes, 4);
+    assert!(analysis.unknown_opcodes.is_empty());
+    assert_eq!(analysis.quirks(), vec![("jump", false)]);
+    // a jump table for SUPER-CHIP goes by Vx
+    let rom = [0x00, 0xFF, 0xB2, 0x08, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0xEE];
+    assert_eq!(analyse(&rom).quirks(), vec![("jump", true)]);
+}
+
+
+#[test]
+fn test_unknown_opcodes() {
+    // 200: JP 204   202: SE V0, 0   204: unknown   206: CLS
+    let analysis = analyse(&[0x12, 0x04, 0x30, 0x00, 0x51, 0x21, 0x00, 0xE0]);
+    assert_eq!(analysis.unknown_opcodes, vec![0x204]);
+    // nothing is followed from an unknown opcode
+    assert_eq!(analysis.reached.iter().copied().collect::<Vec<_>>(), vec![0x200, 0x204]);
+    assert_eq!(analysis.unreached_bytes, 4);
+    assert_eq!(analysis.platform, Platform::Chip8);
+}
+
+
+#[test]
+fn test_addresses() {
+    assert_eq!(addresses(&[0x204, 0x2F0]), "204, 2F0");
+    let addrs: Vec<u16> = (0..9).map(|n| 0x200 + 2 * n).collect();
+    assert_eq!(addresses(&addrs), "200, 202, 204, 206, 208, 20A and 3 more");
+}
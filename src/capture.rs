@@ -0,0 +1,204 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use sdl2::pixels::Color;
+use chippy8::cpu::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use crate::palette::Palette;
+
+pub const DEFAULT_CAPTURE_SCALE: u32 = 8;
+// 4096x2048 images, well inside a gif's 16-bit sizes
+pub const MAX_CAPTURE_SCALE: u32 = 64;
+
+type Vram = [[u8; DISPLAY_WIDTH]; DISPLAY_HEIGHT];
+
+// palette index of every output pixel, each chip-8 pixel repeated scale times
+// in both directions
+fn scaled_indices(vram: &Vram, scale: u32) -> Vec<u8> {
+    let scale = scale as usize;
+    let mut indices = Vec::with_capacity(DISPLAY_WIDTH * DISPLAY_HEIGHT * scale * scale);
+    for row in vram.iter() {
+        let line: Vec<u8> = row.iter()
+            .flat_map(|&pixel| std::iter::repeat_n(pixel & 0x3, scale))
+            .collect();
+        for _ in 0..scale {
+            indices.extend_from_slice(&line);
+        }
+    }
+    indices
+}
+
+fn dimensions(scale: u32) -> (u32, u32) {
+    (DISPLAY_WIDTH as u32 * scale, DISPLAY_HEIGHT as u32 * scale)
+}
+
+fn create(path: &Path) -> Result<BufWriter<File>, String> {
+    File::create(path)
+        .map(BufWriter::new)
+        .map_err(|e| format!("cannot create {}: {}", path.display(), e))
+}
+
+// somewhere in the working directory that won't clobber an earlier capture
+pub fn timestamped_path(prefix: &str, extension: &str) -> PathBuf {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let mut path = PathBuf::from(format!("{}-{}.{}", prefix, seconds, extension));
+    let mut n = 1;
+    while path.exists() {
+        n += 1;
+        path = PathBuf::from(format!("{}-{}-{}.{}", prefix, seconds, n, extension));
+    }
+    path
+}
+
+pub fn save_png(path: &Path, vram: &Vram, palette: &Palette, scale: u32) -> Result<(), String> {
+    let (width, height) = dimensions(scale);
+    let mut encoder = png::Encoder::new(create(path)?, width, height);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(palette.colors.iter().flat_map(|c| vec![c.r, c.g, c.b]).collect::<Vec<_>>());
+    let error = |e: png::EncodingError| format!("cannot write {}: {}", path.display(), e);
+    let mut writer = encoder.write_header().map_err(error)?;
+    writer.write_image_data(&scaled_indices(vram, scale)).map_err(error)?;
+    writer.finish().map_err(error)
+}
+
+// studio-swing BT.601, what ffmpeg assumes for a y4m stream with no colour tags
+fn yuv(color: Color) -> [u8; 3] {
+    let (r, g, b) = (color.r as f32, color.g as f32, color.b as f32);
+    let y = 16.0 + 0.257 * r + 0.504 * g + 0.098 * b;
+    let u = 128.0 - 0.148 * r - 0.291 * g + 0.439 * b;
+    let v = 128.0 + 0.439 * r - 0.368 * g - 0.071 * b;
+    [y.round() as u8, u.round() as u8, v.round() as u8]
+}
+
+// gif delays are whole hundredths of a second, so 60 fps is approximated
+// frame by frame from the running total, giving delays of 2, 1, 2, ...
+fn centiseconds(frames: u64) -> u64 {
+    (frames * 100 + 30) / 60
+}
+
+enum Format {
+    Gif {
+        encoder: gif::Encoder<BufWriter<File>>,
+        // the last frame seen and the frame number it first appeared at;
+        // it's only written once it changes, so runs of identical frames
+        // become one long gif frame
+        pending: Option<(Vec<u8>, u64)>,
+    },
+    Y4m {
+        writer: BufWriter<File>,
+        // y, u and v of each palette colour, one plane at a time
+        planes: [[u8; 4]; 3],
+    },
+}
+
+// writes every frame it's given to an animated gif or a y4m stream for ffmpeg
+pub struct Recorder {
+    path: PathBuf,
+    format: Format,
+    scale: u32,
+    frames: u64,
+}
+
+impl Recorder {
+    // the format comes from the extension, .gif or .y4m
+    pub fn create(path: &Path, palette: &Palette, scale: u32) -> Result<Recorder, String> {
+        let (width, height) = dimensions(scale);
+        if width > u16::MAX as u32 {
+            return Err(format!("capture scale {} is too large to record", scale));
+        }
+        let extension = path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase);
+        let format = match extension.as_deref() {
+            Some("gif") => {
+                let colors: Vec<u8> = palette.colors.iter().flat_map(|c| vec![c.r, c.g, c.b]).collect();
+                let error = |e: gif::EncodingError| format!("cannot write {}: {}", path.display(), e);
+                let mut encoder = gif::Encoder::new(create(path)?, width as u16, height as u16, &colors)
+                    .map_err(error)?;
+                encoder.set_repeat(gif::Repeat::Infinite).map_err(error)?;
+                Format::Gif { encoder, pending: None }
+            }
+            Some("y4m") => {
+                let mut writer = create(path)?;
+                writeln!(writer, "YUV4MPEG2 W{} H{} F60:1 Ip A1:1 C444", width, height)
+                    .map_err(|e| format!("cannot write {}: {}", path.display(), e))?;
+                let mut planes = [[0; 4]; 3];
+                for (index, &color) in palette.colors.iter().enumerate() {
+                    for (plane, sample) in planes.iter_mut().zip(yuv(color)) {
+                        plane[index] = sample;
+                    }
+                }
+                Format::Y4m { writer, planes }
+            }
+            _ => return Err(format!("cannot record to {}, expected a .gif or .y4m file", path.display())),
+        };
+        Ok(Recorder { path: path.to_path_buf(), format, scale, frames: 0 })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn write_frame(&mut self, vram: &Vram) -> Result<(), String> {
+        let indices = scaled_indices(vram, self.scale);
+        let frame = self.frames;
+        self.frames += 1;
+        match &mut self.format {
+            Format::Gif { pending: Some((pixels, _)), .. } if *pixels == indices => Ok(()),
+            Format::Gif { pending, .. } => {
+                let previous = pending.replace((indices, frame));
+                match previous {
+                    Some((pixels, start)) => self.write_gif_frame(&pixels, start, frame),
+                    None => Ok(()),
+                }
+            }
+            Format::Y4m { writer, planes } => {
+                let mut data = Vec::with_capacity(6 + indices.len() * 3);
+                data.extend_from_slice(b"FRAME\n");
+                // planar: all the y samples, then u, then v
+                for plane in planes.iter() {
+                    data.extend(indices.iter().map(|&index| plane[index as usize]));
+                }
+                writer.write_all(&data).map_err(|e| format!("cannot write {}: {}", self.path.display(), e))
+            }
+        }
+    }
+
+    fn write_gif_frame(&mut self, pixels: &[u8], start: u64, end: u64) -> Result<(), String> {
+        let (width, height) = dimensions(self.scale);
+        if let Format::Gif { encoder, .. } = &mut self.format {
+            let delay = centiseconds(end) - centiseconds(start);
+            let frame = gif::Frame {
+                width: width as u16,
+                height: height as u16,
+                buffer: pixels.into(),
+                delay: delay.min(u16::MAX as u64) as u16,
+                ..gif::Frame::default()
+            };
+            encoder.write_frame(&frame).map_err(|e| format!("cannot write {}: {}", self.path.display(), e))?;
+        }
+        Ok(())
+    }
+
+    // flushes the last frame; dropping a recorder without finishing it can
+    // leave a truncated file
+    pub fn finish(mut self) -> Result<(), String> {
+        let end = self.frames;
+        let pending = match &mut self.format {
+            Format::Gif { pending, .. } => pending.take(),
+            Format::Y4m { .. } => None,
+        };
+        if let Some((pixels, start)) = pending {
+            self.write_gif_frame(&pixels, start, end)?;
+        }
+        let path = self.path.clone();
+        let error = |e: std::io::Error| format!("cannot write {}: {}", path.display(), e);
+        match self.format {
+            Format::Gif { encoder, .. } => encoder.into_inner().map_err(error)?.flush().map_err(error),
+            Format::Y4m { mut writer, .. } => writer.flush().map_err(error),
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "./capture_tests.rs"]
+mod capture_tests;
@@ -0,0 +1,102 @@
+use super::*;
+use std::env;
+use std::fs;
+use std::process;
+
+fn vram(lit: &[(usize, usize, u8)]) -> Vram {
+    let mut vram = [[0; DISPLAY_WIDTH]; DISPLAY_HEIGHT];
+    for &(x, y, pixel) in lit {
+        vram[y][x] = pixel;
+    }
+    vram
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("chippy8-capture-{}-{}", process::id(), name))
+}
+
+
+#[test]
+fn test_scaled_indices() {
+    let vram = vram(&[(0, 0, 1), (63, 0, 2), (1, 31, 0xff)]);
+    let indices = scaled_indices(&vram, 1);
+    assert_eq!(indices.len(), DISPLAY_WIDTH * DISPLAY_HEIGHT);
+    assert_eq!((indices[0], indices[63], indices[31 * 64 + 1]), (1, 2, 3));
+    let indices = scaled_indices(&vram, 3);
+    let width = DISPLAY_WIDTH * 3;
+    assert_eq!(indices.len(), width * DISPLAY_HEIGHT * 3);
+    // each chip-8 pixel is a 3x3 block
+    for y in 0..3 {
+        assert_eq!(&indices[y * width..y * width + 4], &[1, 1, 1, 0]);
+        assert_eq!(&indices[y * width + width - 4..(y + 1) * width], &[0, 2, 2, 2]);
+    }
+    assert_eq!(indices[3 * width], 0);
+    assert_eq!(indices.iter().filter(|&&index| index == 3).count(), 9);
+    assert_eq!(dimensions(3), (192, 96));
+}
+
+
+#[test]
+fn test_centiseconds() {
+    let delays: Vec<u64> = (0..6).map(|frame| centiseconds(frame + 1) - centiseconds(frame)).collect();
+    assert_eq!(delays, vec![2, 1, 2, 2, 1, 2]);
+    // never more than half a hundredth off, so a second is a second
+    assert_eq!(centiseconds(60), 100);
+    assert_eq!(centiseconds(3600), 6000);
+}
+
+
+#[test]
+fn test_yuv() {
+    assert_eq!(yuv(Color::RGB(0, 0, 0)), [16, 128, 128]);
+    assert_eq!(yuv(Color::RGB(255, 255, 255)), [235, 128, 128]);
+}
+
+
+#[test]
+fn test_gif_merges_identical_frames() {
+    let path = temp_path("merge.gif");
+    let (blank, dot) = (vram(&[]), vram(&[(5, 5, 1)]));
+    let mut recorder = Recorder::create(&path, &Palette::default(), 1).unwrap();
+    for frame in [&blank, &blank, &blank, &dot, &blank].iter() {
+        recorder.write_frame(frame).unwrap();
+    }
+    recorder.finish().unwrap();
+    let mut decoder = gif::DecodeOptions::new().read_info(fs::File::open(&path).unwrap()).unwrap();
+    let mut delays = Vec::new();
+    while let Some(frame) = decoder.read_next_frame().unwrap() {
+        delays.push(frame.delay);
+    }
+    // three blank frames as one, with their delays summed
+    assert_eq!(delays, vec![5, 2, 1]);
+    assert_eq!(delays.iter().sum::<u16>() as u64, centiseconds(5));
+    fs::remove_file(path).unwrap();
+}
+
+
+#[test]
+fn test_y4m() {
+    let path = temp_path("frames.y4m");
+    let mut recorder = Recorder::create(&path, &Palette::named("high-contrast").unwrap(), 2).unwrap();
+    recorder.write_frame(&vram(&[(0, 0, 1)])).unwrap();
+    recorder.write_frame(&vram(&[])).unwrap();
+    recorder.finish().unwrap();
+    let data = fs::read(&path).unwrap();
+    let header = b"YUV4MPEG2 W128 H64 F60:1 Ip A1:1 C444\n";
+    let frame_length = b"FRAME\n".len() + 128 * 64 * 3;
+    assert_eq!(&data[..header.len()], &header[..]);
+    assert_eq!(data.len(), header.len() + 2 * frame_length);
+    // the top left 2x2 is white in the first frame, and nothing is in the second
+    let first = &data[header.len() + 6..];
+    assert_eq!(&first[..3], &[235, 235, 16]);
+    assert_eq!(first[128], 235);
+    assert_eq!(data[header.len() + frame_length + 6], 16);
+    fs::remove_file(path).unwrap();
+}
+
+
+#[test]
+fn test_unknown_extension() {
+    let error = Recorder::create(Path::new("capture.avi"), &Palette::default(), 1).err().unwrap();
+    assert_eq!(error, "cannot record to capture.avi, expected a .gif or .y4m file");
+}
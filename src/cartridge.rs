@@ -1,21 +1,271 @@
-use std::fs::File;
-use std::io::prelude::*;
-use std::io;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek};
+use std::path::Path;
+use flate2::read::GzDecoder;
+use zip::ZipArchive;
+use sha1::{Digest, Sha1};
+use crate::analyser::opcode_platform;
 
+// what fits between the reset vector and the end of 4K of ram
 pub const MAX_ROM_SIZE: usize = 0xE00;
+// XO-CHIP has 64K of ram
+pub const MAX_XOCHIP_ROM_SIZE: usize = 0xFE00;
+
+// in the order they came out, each adding instructions
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Platform {
+    Chip8,
+    SuperChip,
+    XoChip,
+}
+
+impl Platform {
+    // chip-8-database platform ids, e.g. "originalChip8" or "superchip1"
+    pub fn from_database_id(id: &str) -> Platform {
+        match id {
+            "xochip" => Platform::XoChip,
+            "chip48" | "superchip1" | "superchip" | "megachip8" => Platform::SuperChip,
+            _ => Platform::Chip8,
+        }
+    }
+
+    pub fn max_rom_size(self) -> usize {
+        match self {
+            Platform::Chip8 | Platform::SuperChip => MAX_ROM_SIZE,
+            Platform::XoChip => MAX_XOCHIP_ROM_SIZE,
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Platform::Chip8 => "CHIP-8",
+            Platform::SuperChip => "SUPER-CHIP",
+            Platform::XoChip => "XO-CHIP",
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum CartridgeError {
+    NotFound,
+    IsDirectory,
+    Empty,
+    // platform is None when it's this emulator's memory that's too small
+    TooLarge { size: usize, max: usize, platform: Option<Platform> },
+    Unreadable(io::Error),
+    // a zip holding something other than a rom, or not a real zip or gzip
+    BadArchive(String),
+    NoRoms,
+    // a zip of more than one rom, and no entry picked; their names
+    SeveralRoms(Vec<String>),
+    NoSuchEntry(String),
+}
+
+impl fmt::Display for CartridgeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CartridgeError::NotFound => write!(f, "no such file"),
+            CartridgeError::IsDirectory => write!(f, "it's a directory, not a rom"),
+            CartridgeError::Empty => write!(f, "the file is empty"),
+            CartridgeError::TooLarge { size, max, platform: Some(platform) } => {
+                write!(f, "{} bytes is too large for {}, which fits at most {}", size, platform, max)
+            }
+            CartridgeError::TooLarge { size, max, platform: None } => {
+                write!(f, "{} bytes is too large, at most {} fit in memory", size, max)
+            }
+            CartridgeError::Unreadable(e) => write!(f, "unreadable: {}", e),
+            CartridgeError::BadArchive(e) => write!(f, "broken archive: {}", e),
+            CartridgeError::NoRoms => write!(f, "the archive has no roms in it"),
+            CartridgeError::SeveralRoms(names) => {
+                write!(f, "the archive has several roms, pick one with --entry: {}", names.join(", "))
+            }
+            CartridgeError::NoSuchEntry(name) => write!(f, "the archive has no rom called '{}'", name),
+        }
+    }
+}
+
+impl std::error::Error for CartridgeError {}
 
 pub struct Cartridge {
     pub rom: Vec<u8>,
+    // hex sha1 of the rom, to key per-rom settings
+    pub sha1: String,
+    // a guess from the instructions the rom contains
+    pub platform: Platform,
+    // the file in the zip it came from
+    pub entry: Option<String>,
+    // things that don't stop it running but might explain why it goes wrong
+    pub warnings: Vec<String>,
+}
+
+// extensions roms go by; anything else in a zip is taken for a readme or
+// artwork, unless there's nothing else
+const ROM_EXTENSIONS: [&str; 6] = ["ch8", "c8", "sc8", "xo8", "c8x", "hc8"];
+
+fn has_extension(name: &str, extensions: &[&str]) -> bool {
+    Path::new(name).extension()
+        .and_then(|e| e.to_str())
+        .map(|e| extensions.iter().any(|rom| e.eq_ignore_ascii_case(rom)))
+        .unwrap_or(false)
+}
+
+// what the launcher lists: roms by extension, and archives that may hold them
+pub fn is_rom_file(path: &Path) -> bool {
+    let name = path.to_string_lossy();
+    has_extension(&name, &ROM_EXTENSIONS) || has_extension(&name, &["zip", "gz"])
+}
+
+fn file_name(entry: &str) -> &str {
+    entry.rsplit('/').next().unwrap_or(entry)
+}
+
+// more than any platform's memory can hold is an error anyway, so there's
+// no need to unpack any further than that
+fn read_limited(reader: impl Read) -> Result<Vec<u8>, CartridgeError> {
+    let mut rom = Vec::new();
+    reader.take(MAX_XOCHIP_ROM_SIZE as u64 + 1)
+        .read_to_end(&mut rom)
+        .map_err(|e| CartridgeError::BadArchive(e.to_string()))?;
+    Ok(rom)
 }
 
+fn bad_zip(e: zip::result::ZipError) -> CartridgeError {
+    match e {
+        zip::result::ZipError::Io(e) => CartridgeError::Unreadable(e),
+        e => CartridgeError::BadArchive(e.to_string()),
+    }
+}
+
+// the roms in a zip, leaving out directories and the metadata some
+// archivers add
+fn zip_roms<R: Read + Seek>(archive: &ZipArchive<R>) -> Vec<String> {
+    let files: Vec<String> = archive.file_names()
+        .filter(|name| !name.ends_with('/'))
+        .filter(|name| !name.starts_with("__MACOSX/") && !file_name(name).starts_with('.'))
+        .map(String::from)
+        .collect();
+    let mut roms: Vec<String> = files.iter()
+        .filter(|name| has_extension(name, &ROM_EXTENSIONS))
+        .cloned()
+        .collect();
+    if roms.is_empty() {
+        roms = files;
+    }
+    roms.sort();
+    roms
+}
+
+// entries can be picked by their full path in the zip, or just the file
+// name if that's unique
+fn find_entry(roms: &[String], wanted: &str) -> Option<String> {
+    if let Some(name) = roms.iter().find(|name| name.as_str() == wanted) {
+        return Some(name.clone());
+    }
+    let mut matches = roms.iter().filter(|name| file_name(name).eq_ignore_ascii_case(wanted));
+    match (matches.next(), matches.next()) {
+        (Some(name), None) => Some(name.clone()),
+        _ => None,
+    }
+}
+
+// the newest platform any instruction in the rom needs. this reads every
+// word, data included, so it can guess too high but never too low for
+// code that's word aligned.
+fn detect_platform(rom: &[u8]) -> Platform {
+    rom.chunks_exact(2)
+        .filter_map(|word| opcode_platform(u16::from_be_bytes([word[0], word[1]])))
+        .max()
+        .unwrap_or(Platform::Chip8)
+}
 
 impl Cartridge {
-    pub fn new(rom_file_path: &str) -> io::Result<Self> {
-        let mut rom_file = File::open(rom_file_path).expect(&format!("{} not found", rom_file_path));
-        let mut buffer = Vec::new();
-        rom_file.read_to_end(&mut buffer)?;
+    // .zip and .gz files are unpacked; entry picks the rom out of a zip
+    // that holds more than one
+    pub fn new<P: AsRef<Path>>(rom_file_path: P, entry: Option<&str>) -> Result<Self, CartridgeError> {
+        let path = rom_file_path.as_ref();
+        let metadata = fs::metadata(path).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => CartridgeError::NotFound,
+            _ => CartridgeError::Unreadable(e),
+        })?;
+        if metadata.is_dir() {
+            return Err(CartridgeError::IsDirectory);
+        }
+        let file_name = path.to_string_lossy();
+        if has_extension(&file_name, &["zip"]) {
+            return Self::from_zip(path, entry);
+        }
+        if has_extension(&file_name, &["gz"]) {
+            return Self::from_gzip(File::open(path).map_err(CartridgeError::Unreadable)?);
+        }
+        Self::from_bytes(fs::read(path).map_err(CartridgeError::Unreadable)?)
+    }
+
+    fn from_gzip(reader: impl Read) -> Result<Self, CartridgeError> {
+        Self::from_bytes(read_limited(GzDecoder::new(reader))?)
+    }
+
+    fn from_zip(path: &Path, entry: Option<&str>) -> Result<Self, CartridgeError> {
+        Self::from_zip_reader(File::open(path).map_err(CartridgeError::Unreadable)?, entry)
+    }
+
+    fn from_zip_reader(reader: impl Read + Seek, entry: Option<&str>) -> Result<Self, CartridgeError> {
+        let mut archive = ZipArchive::new(reader).map_err(bad_zip)?;
+        let roms = zip_roms(&archive);
+        let name = match (entry, roms.as_slice()) {
+            (_, []) => return Err(CartridgeError::NoRoms),
+            (Some(wanted), _) => find_entry(&roms, wanted)
+                .ok_or_else(|| CartridgeError::NoSuchEntry(wanted.to_string()))?,
+            (None, [only]) => only.clone(),
+            (None, _) => return Err(CartridgeError::SeveralRoms(roms)),
+        };
+        let rom = read_limited(archive.by_name(&name).map_err(bad_zip)?)?;
+        let mut cart = Self::from_bytes(rom)?;
+        cart.entry = Some(name);
+        Ok(cart)
+    }
+
+    pub fn from_bytes(rom: Vec<u8>) -> Result<Self, CartridgeError> {
+        if rom.is_empty() {
+            return Err(CartridgeError::Empty);
+        }
+        if rom.len() > MAX_XOCHIP_ROM_SIZE {
+            return Err(CartridgeError::TooLarge { size: rom.len(), max: MAX_XOCHIP_ROM_SIZE, platform: None });
+        }
+        let sha1 = Sha1::digest(&rom)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        let platform = detect_platform(&rom);
+        let mut warnings = Vec::new();
+        // instructions are two bytes, so an odd size usually means a
+        // truncated or padded file
+        if rom.len() % 2 == 1 {
+            warnings.push(format!("the rom is an odd {} bytes long, it may be truncated", rom.len()));
+        }
         Ok(Cartridge {
-            rom: buffer,
+            rom,
+            sha1,
+            platform,
+            entry: None,
+            warnings,
         })
     }
+
+    pub fn size(&self) -> usize {
+        self.rom.len()
+    }
+
+    pub fn check_fits(&self, platform: Platform) -> Result<(), CartridgeError> {
+        if self.size() > platform.max_rom_size() {
+            return Err(CartridgeError::TooLarge { size: self.size(), max: platform.max_rom_size(), platform: Some(platform) });
+        }
+        Ok(())
+    }
 }
+
+#[cfg(test)]
+#[path = "./cartridge_tests.rs"]
+mod cartridge_tests;
@@ -0,0 +1,156 @@
+use super::*;
+use std::env;
+use std::path::PathBuf;
+use std::process;
+
+// a directory of its own for each test
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("chippy8-cartridge-{}-{}", process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+
+#[test]
+fn test_new() {
+    let dir = temp_dir("new");
+    assert!(matches!(Cartridge::new(dir.join("missing.ch8"), None), Err(CartridgeError::NotFound)));
+    assert!(matches!(Cartridge::new(&dir, None), Err(CartridgeError::IsDirectory)));
+    let path = dir.join("empty.ch8");
+    fs::write(&path, []).unwrap();
+    assert!(matches!(Cartridge::new(&path, None), Err(CartridgeError::Empty)));
+    let path = dir.join("jump.ch8");
+    fs::write(&path, [0x12, 0x00]).unwrap();
+    let cart = Cartridge::new(&path, None).unwrap();
+    assert_eq!(cart.rom, vec![0x12, 0x00]);
+    assert_eq!(cart.sha1, "92a5652d382a18e89c4881ec57041fc7d885ca80");
+    assert_eq!(cart.entry, None);
+    assert!(cart.warnings.is_empty());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+
+#[test]
+fn test_sizes() {
+    assert!(matches!(Cartridge::from_bytes(vec![]), Err(CartridgeError::Empty)));
+    let too_large = Cartridge::from_bytes(vec![0; MAX_XOCHIP_ROM_SIZE + 1]);
+    assert!(matches!(too_large, Err(CartridgeError::TooLarge { size, max: MAX_XOCHIP_ROM_SIZE, platform: None })
+                     if size == MAX_XOCHIP_ROM_SIZE + 1));
+    // 4K platforms fit less than XO-CHIP's 64K
+    let cart = Cartridge::from_bytes(vec![0; MAX_ROM_SIZE + 2]).unwrap();
+    for platform in [Platform::Chip8, Platform::SuperChip] {
+        assert!(matches!(cart.check_fits(platform),
+                         Err(CartridgeError::TooLarge { max: MAX_ROM_SIZE, platform: Some(p), .. }) if p == platform));
+    }
+    assert!(cart.check_fits(Platform::XoChip).is_ok());
+    let cart = Cartridge::from_bytes(vec![0; MAX_ROM_SIZE]).unwrap();
+    assert!(cart.check_fits(Platform::Chip8).is_ok());
+}
+
+
+#[test]
+fn test_odd_length() {
+    let cart = Cartridge::from_bytes(vec![0x00, 0xE0, 0x12]).unwrap();
+    assert_eq!(cart.warnings, vec!["the rom is an odd 3 bytes long, it may be truncated".to_string()]);
+    assert!(Cartridge::from_bytes(vec![0x00, 0xE0]).unwrap().warnings.is_empty());
+}
+
+
+#[test]
+fn test_detect_platform() {
+    assert_eq!(detect_platform(&[0x00, 0xE0, 0x12, 0x00]), Platform::Chip8);
+    // 00FF: hires
+    assert_eq!(detect_platform(&[0x00, 0xFF, 0x12, 0x00]), Platform::SuperChip);
+    // F000 nnnn: long load of I, newest wins
+    assert_eq!(detect_platform(&[0x00, 0xFF, 0xF0, 0x00, 0x12, 0x00]), Platform::XoChip);
+    // a trailing odd byte isn't an instruction
+    assert_eq!(detect_platform(&[0x12, 0x00, 0xF0]), Platform::Chip8);
+}
+
+
+// a zip of the given files, built in memory
+fn zip(files: &[(&str, &[u8])]) -> io::Cursor<Vec<u8>> {
+    use std::io::Write;
+    let mut writer = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+    for (name, data) in files {
+        writer.start_file(*name, zip::write::FileOptions::default()).unwrap();
+        writer.write_all(data).unwrap();
+    }
+    let mut zip = writer.finish().unwrap();
+    zip.set_position(0);
+    zip
+}
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    use std::io::Write;
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+
+#[test]
+fn test_zip_entries() {
+    let pong: &[u8] = &[0x12, 0x00];
+    let tetris: &[u8] = &[0x00, 0xE0, 0x12, 0x02];
+    // readmes, artwork and archiver droppings are left out when there are roms
+    let archive = || zip(&[
+        ("README.txt", b"hello"),
+        ("__MACOSX/games/._pong.ch8", b"junk"),
+        ("games/.hidden.ch8", b"junk"),
+        ("games/pong.ch8", pong),
+        ("games/tetris.CH8", tetris),
+    ]);
+    let roms = zip_roms(&ZipArchive::new(archive()).unwrap());
+    assert_eq!(roms, vec!["games/pong.ch8".to_string(), "games/tetris.CH8".to_string()]);
+
+    match Cartridge::from_zip_reader(archive(), None) {
+        Err(CartridgeError::SeveralRoms(names)) => assert_eq!(names, roms),
+        _ => panic!("expected several roms"),
+    }
+    // by full path, or by a unique file name in any case
+    let cart = Cartridge::from_zip_reader(archive(), Some("games/pong.ch8")).unwrap();
+    assert_eq!((cart.rom.as_slice(), cart.entry.as_deref()), (pong, Some("games/pong.ch8")));
+    let cart = Cartridge::from_zip_reader(archive(), Some("tetris.ch8")).unwrap();
+    assert_eq!((cart.rom.as_slice(), cart.entry.as_deref()), (tetris, Some("games/tetris.CH8")));
+    assert!(matches!(Cartridge::from_zip_reader(archive(), Some("README.txt")),
+                     Err(CartridgeError::NoSuchEntry(name)) if name == "README.txt"));
+
+    // a single rom needs no entry
+    let cart = Cartridge::from_zip_reader(zip(&[("notes.txt", b"hi"), ("pong.ch8", pong)]), None).unwrap();
+    assert_eq!(cart.entry.as_deref(), Some("pong.ch8"));
+    // with no rom extensions anywhere, any file will do
+    let cart = Cartridge::from_zip_reader(zip(&[("PONG", pong)]), None).unwrap();
+    assert_eq!(cart.entry.as_deref(), Some("PONG"));
+    assert!(matches!(Cartridge::from_zip_reader(zip(&[]), None), Err(CartridgeError::NoRoms)));
+    assert!(matches!(Cartridge::from_zip_reader(io::Cursor::new(b"not a zip".to_vec()), None),
+                     Err(CartridgeError::BadArchive(_))));
+}
+
+
+#[test]
+fn test_decompression_limit() {
+    // a few K of zip or gzip unpacking to more than any platform holds
+    // stops at the limit rather than filling memory
+    let bomb = vec![0; 16 * MAX_XOCHIP_ROM_SIZE];
+    let too_large = |result| matches!(result, Err(CartridgeError::TooLarge { size, .. }) if size == MAX_XOCHIP_ROM_SIZE + 1);
+    assert!(too_large(Cartridge::from_gzip(gzip(&bomb).as_slice())));
+    assert!(too_large(Cartridge::from_zip_reader(zip(&[("bomb.ch8", &bomb)]), None)));
+    let cart = Cartridge::from_gzip(gzip(&[0x12, 0x00]).as_slice()).unwrap();
+    assert_eq!(cart.rom, vec![0x12, 0x00]);
+    assert!(matches!(Cartridge::from_gzip(&b"not gzip"[..]), Err(CartridgeError::BadArchive(_))));
+}
+
+
+#[test]
+fn test_archive_files() {
+    let dir = temp_dir("archives");
+    let path = dir.join("pong.zip");
+    fs::write(&path, zip(&[("pong.ch8", &[0x12, 0x00])]).into_inner()).unwrap();
+    assert_eq!(Cartridge::new(&path, None).unwrap().entry.as_deref(), Some("pong.ch8"));
+    let path = dir.join("pong.ch8.gz");
+    fs::write(&path, gzip(&[0x12, 0x00])).unwrap();
+    assert_eq!(Cartridge::new(&path, None).unwrap().rom, vec![0x12, 0x00]);
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,249 @@
+use std::path::PathBuf;
+use sdl2::pixels::Color;
+use crate::capture::MAX_CAPTURE_SCALE;
+use crate::palette::{parse_hex_color, Palette};
+use crate::pacer::Pacing;
+use chippy8::quirks::Quirks;
+use chippy8::script::Press;
+use crate::savestate::SLOTS;
+
+// window pixels per chip-8 pixel; 64 is already a 4096x2048 window
+const MAX_SCALE: u32 = 64;
+
+pub const USAGE: &str = "\
+usage: chippy8 [run] [rom] [options]
+       chippy8 disasm <rom>
+       chippy8 info <rom>
+       chippy8 bench <rom> [--frames N] [options]
+       chippy8 diff <rom> [--left SIDE] [--right SIDE] [--input KEY@FRAMES]...
+
+roms can be zip or gzip files. with no rom, run opens a launcher listing
+recent roms and those under rom_dir in the config.
+
+options:
+  --entry NAME        the rom to run from a zip of several, by name
+  --scale N           window pixels per chip-8 pixel, 1 to 64 (default 16)
+  --fullscreen        start fullscreen
+  --fg COLOR          lit pixel colour, as #rrggbb
+  --bg COLOR          unlit pixel colour, as #rrggbb
+  --ipf N             instructions per frame
+  --vip               COSMAC VIP cycle timing instead of a flat --ipf
+  --uncapped          run frames back to back as fast as the host allows
+  --fast-forward N    frames fast-forward (held tab) and turbo (`) run per
+                      host frame; 0, the default, runs as many as fit
+  --pacing MODE       what keeps time at 60 frames a second: wall (the
+                      default), audio, the sound card's sample clock, or
+                      uncapped, the same as --uncapped
+  --quirks PRESET     cowgod, chip8, modern, chip48, schip or xochip
+  --mute              start with sound off
+  --volume N          beeper volume, 0 to 100
+  --seed N            seed the random number generator
+  --config PATH       config file to use instead of the default
+  --frames N          quit after N frames (bench default 3600)
+  --screenshot PATH   save the last frame as a png on exit
+  --record PATH       record every frame to a .gif or .y4m file
+  --wav PATH          write the beeper's output to a wav file, in emulated
+                      time; works with bench for runs without a window
+  --capture-scale N   image pixels per chip-8 pixel for captures, 1 to 64
+                      (default 8)
+  --capture-palette NAME
+                      palette for captures instead of the window's
+  --watch             reload the rom whenever its file changes
+  --watch-state N     after a reload, load save state slot N (0-9)
+  --watch-keep-ram    after a reload, keep the ram outside the program
+  --left SIDE         for diff, what runs on each side: a quirk preset for
+  --right SIDE        the emulator, or reference or reference:PRESET for
+                      the reference interpreter (default --quirks, against
+                      the reference with the same quirks)
+  --input KEY@FRAMES  for diff, hold a key, as 5@30 or 5@30-45; repeatable
+  -h, --help          show this help";
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Run(Options),
+    Disasm(Options),
+    Info(Options),
+    Bench(Options),
+    Diff(Options),
+    Help,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Options {
+    // none opens the launcher
+    pub rom: Option<PathBuf>,
+    pub entry: Option<String>,
+    pub scale: Option<u32>,
+    pub fullscreen: bool,
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub instructions_per_frame: Option<u32>,
+    pub vip: bool,
+    pub uncapped: bool,
+    pub fast_forward: Option<u32>,
+    pub pacing: Option<Pacing>,
+    pub quirks: Option<Quirks>,
+    pub mute: bool,
+    pub volume: Option<f32>,
+    pub seed: Option<u64>,
+    pub config: Option<PathBuf>,
+    pub frames: Option<u32>,
+    pub screenshot: Option<PathBuf>,
+    pub record: Option<PathBuf>,
+    pub wav: Option<PathBuf>,
+    pub capture_scale: Option<u32>,
+    pub capture_palette: Option<Palette>,
+    pub watch: bool,
+    pub watch_state: Option<u32>,
+    pub watch_keep_ram: bool,
+    pub left: Option<Side>,
+    pub right: Option<Side>,
+    pub input: Vec<String>,
+}
+
+// one of the two machines diff runs
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Side {
+    Emulator(Quirks),
+    Reference(Quirks),
+}
+
+impl Side {
+    // PRESET, reference, or reference:PRESET
+    fn parse(text: &str, quirks: Quirks) -> Option<Side> {
+        match text.split_once(':') {
+            Some(("reference", preset)) => Quirks::preset(preset).map(Side::Reference),
+            None if text == "reference" => Some(Side::Reference(quirks)),
+            None => Quirks::preset(text).map(Side::Emulator),
+            _ => None,
+        }
+    }
+}
+
+fn value<'a>(option: &str, args: &mut impl Iterator<Item = &'a String>) -> Result<&'a String, String> {
+    args.next().ok_or_else(|| format!("{} needs a value", option))
+}
+
+fn number<'a, T: std::str::FromStr>(option: &str, args: &mut impl Iterator<Item = &'a String>) -> Result<T, String> {
+    let text = value(option, args)?;
+    text.parse().map_err(|_| format!("invalid number '{}' for {}", text, option))
+}
+
+fn color<'a>(option: &str, args: &mut impl Iterator<Item = &'a String>) -> Result<Color, String> {
+    let text = value(option, args)?;
+    parse_hex_color(text).ok_or_else(|| format!("invalid colour '{}' for {}, expected #rrggbb", text, option))
+}
+
+// args without the program name
+pub fn parse(args: &[String]) -> Result<Command, String> {
+    let mut args = args.iter().peekable();
+    let subcommand = match args.peek().map(|arg| arg.as_str()) {
+        Some("run") | Some("disasm") | Some("info") | Some("bench") | Some("diff") => args.next().map(String::as_str),
+        _ => None,
+    };
+    let mut options = Options::default();
+    let mut rom = None;
+    let mut sides = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--entry" => options.entry = Some(value(arg, &mut args)?.clone()),
+            "--scale" => options.scale = Some(number(arg, &mut args)?),
+            "--fullscreen" => options.fullscreen = true,
+            "--fg" => options.fg = Some(color(arg, &mut args)?),
+            "--bg" => options.bg = Some(color(arg, &mut args)?),
+            "--ipf" => options.instructions_per_frame = Some(number(arg, &mut args)?),
+            "--vip" => options.vip = true,
+            "--uncapped" => options.uncapped = true,
+            "--fast-forward" => options.fast_forward = Some(number(arg, &mut args)?),
+            "--pacing" => {
+                let name = value(arg, &mut args)?;
+                options.pacing = Some(Pacing::from_name(name)
+                    .ok_or_else(|| format!("unknown pacing '{}', expected wall, audio or uncapped", name))?);
+            }
+            "--quirks" => {
+                let preset = value(arg, &mut args)?;
+                options.quirks = Some(Quirks::preset(preset)
+                    .ok_or_else(|| format!("unknown quirk preset '{}'", preset))?);
+            }
+            "--mute" => options.mute = true,
+            "--volume" => {
+                let volume: f32 = number(arg, &mut args)?;
+                if !(0.0..=100.0).contains(&volume) {
+                    return Err(format!("volume {} is out of range, expected 0 to 100", volume));
+                }
+                options.volume = Some(volume / 100.0);
+            }
+            "--seed" => options.seed = Some(number(arg, &mut args)?),
+            "--config" => options.config = Some(PathBuf::from(value(arg, &mut args)?)),
+            "--frames" => options.frames = Some(number(arg, &mut args)?),
+            "--screenshot" => options.screenshot = Some(PathBuf::from(value(arg, &mut args)?)),
+            "--record" => options.record = Some(PathBuf::from(value(arg, &mut args)?)),
+            "--wav" => options.wav = Some(PathBuf::from(value(arg, &mut args)?)),
+            "--capture-scale" => options.capture_scale = Some(number(arg, &mut args)?),
+            "--capture-palette" => {
+                let name = value(arg, &mut args)?;
+                options.capture_palette = Some(Palette::named(name)
+                    .ok_or_else(|| format!("unknown palette '{}'", name))?);
+            }
+            "--watch" => options.watch = true,
+            "--watch-state" => options.watch_state = Some(number(arg, &mut args)?),
+            "--watch-keep-ram" => options.watch_keep_ram = true,
+            "--left" | "--right" => {
+                sides.push((arg.as_str(), value(arg, &mut args)?));
+            }
+            "--input" => {
+                let input = value(arg, &mut args)?;
+                Press::parse(input)?;
+                options.input.push(input.clone());
+            }
+            option if option.starts_with('-') => return Err(format!("unknown option '{}'", option)),
+            path => {
+                if rom.is_some() {
+                    return Err(format!("unexpected argument '{}'", path));
+                }
+                rom = Some(PathBuf::from(path));
+            }
+        }
+    }
+    if rom.is_none() && subcommand.is_some() && subcommand != Some("run") {
+        return Err("no rom given".to_string());
+    }
+    options.rom = rom;
+    // after the loop, so reference takes --quirks wherever it was given
+    for (option, text) in sides {
+        let side = Side::parse(text, options.quirks.unwrap_or_default())
+            .ok_or_else(|| format!("invalid {} '{}', expected a quirk preset, reference or reference:PRESET", option, text))?;
+        if option == "--left" {
+            options.left = Some(side);
+        } else {
+            options.right = Some(side);
+        }
+    }
+    if options.scale.is_some_and(|scale| scale == 0 || scale > MAX_SCALE) {
+        return Err(format!("--scale must be from 1 to {}", MAX_SCALE));
+    }
+    if options.capture_scale.is_some_and(|scale| scale == 0 || scale > MAX_CAPTURE_SCALE) {
+        return Err(format!("--capture-scale must be from 1 to {}", MAX_CAPTURE_SCALE));
+    }
+    if options.watch_state.is_some_and(|slot| slot >= SLOTS) {
+        return Err(format!("--watch-state must be below {}", SLOTS));
+    }
+    if options.watch_state.is_some() && options.watch_keep_ram {
+        return Err("--watch-state and --watch-keep-ram can't be used together".to_string());
+    }
+    if (options.watch_state.is_some() || options.watch_keep_ram) && !options.watch {
+        return Err("--watch-state and --watch-keep-ram need --watch".to_string());
+    }
+    Ok(match subcommand {
+        Some("disasm") => Command::Disasm(options),
+        Some("info") => Command::Info(options),
+        Some("bench") => Command::Bench(options),
+        Some("diff") => Command::Diff(options),
+        _ => Command::Run(options),
+    })
+}
+
+#[cfg(test)]
+#[path = "./cli_tests.rs"]
+mod cli_tests;
@@ -0,0 +1,107 @@
+use super::*;
+use chippy8::quirks::{CHIP48, COWGOD, SUPERCHIP};
+
+fn parse_args(args: &[&str]) -> Result<Command, String> {
+    let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    parse(&args)
+}
+
+fn with_rom(rom: &str) -> Options {
+    Options { rom: Some(PathBuf::from(rom)), ..Options::default() }
+}
+
+
+#[test]
+fn test_subcommands() {
+    let cases: &[(&[&str], Command)] = &[
+        (&[], Command::Run(Options::default())),
+        (&["run"], Command::Run(Options::default())),
+        (&["pong.ch8"], Command::Run(with_rom("pong.ch8"))),
+        (&["run", "pong.ch8"], Command::Run(with_rom("pong.ch8"))),
+        (&["disasm", "pong.ch8"], Command::Disasm(with_rom("pong.ch8"))),
+        (&["info", "pong.ch8"], Command::Info(with_rom("pong.ch8"))),
+        (&["bench", "pong.ch8"], Command::Bench(with_rom("pong.ch8"))),
+        (&["diff", "pong.ch8"], Command::Diff(with_rom("pong.ch8"))),
+        // only the first argument can be a subcommand
+        (&["pong.ch8", "--help"], Command::Help),
+        (&["bench", "-h"], Command::Help),
+    ];
+    for (args, expected) in cases {
+        assert_eq!(parse_args(args).as_ref(), Ok(expected), "{:?}", args);
+    }
+}
+
+
+#[test]
+fn test_options() {
+    let expected = Options {
+        rom: Some(PathBuf::from("pong.ch8")),
+        scale: Some(4),
+        instructions_per_frame: Some(20),
+        quirks: Some(SUPERCHIP),
+        volume: Some(0.5),
+        seed: Some(7),
+        frames: Some(100),
+        watch: true,
+        watch_state: Some(3),
+        ..Options::default()
+    };
+    let args = ["bench", "--scale", "4", "--ipf", "20", "pong.ch8", "--quirks", "schip", "--volume", "50",
+                "--seed", "7", "--frames", "100", "--watch", "--watch-state", "3"];
+    assert_eq!(parse_args(&args), Ok(Command::Bench(expected)));
+}
+
+
+#[test]
+fn test_errors() {
+    let cases: &[(&[&str], &str)] = &[
+        (&["disasm"], "no rom given"),
+        (&["info", "--frames", "10"], "no rom given"),
+        (&["bench"], "no rom given"),
+        (&["diff"], "no rom given"),
+        (&["a.ch8", "b.ch8"], "unexpected argument 'b.ch8'"),
+        (&["--bogus"], "unknown option '--bogus'"),
+        (&["--scale"], "--scale needs a value"),
+        (&["--scale", "big"], "invalid number 'big' for --scale"),
+        (&["--volume", "101"], "volume 101 is out of range, expected 0 to 100"),
+        (&["--volume", "-1"], "volume -1 is out of range, expected 0 to 100"),
+        (&["--scale", "0"], "--scale must be from 1 to 64"),
+        (&["--scale", "65"], "--scale must be from 1 to 64"),
+        (&["--scale", "4294967295"], "--scale must be from 1 to 64"),
+        (&["--capture-scale", "0"], "--capture-scale must be from 1 to 64"),
+        (&["--capture-scale", "100000"], "--capture-scale must be from 1 to 64"),
+        (&["--pacing", "bogus"], "unknown pacing 'bogus', expected wall, audio or uncapped"),
+        (&["--quirks", "bogus"], "unknown quirk preset 'bogus'"),
+        (&["--watch", "--watch-state", "10"], "--watch-state must be below 10"),
+        (&["--watch", "--watch-state", "1", "--watch-keep-ram"], "--watch-state and --watch-keep-ram can't be used together"),
+        (&["--watch-state", "1"], "--watch-state and --watch-keep-ram need --watch"),
+        (&["--watch-keep-ram"], "--watch-state and --watch-keep-ram need --watch"),
+        (&["--input", "g@1"], "invalid input 'g@1', expected key@frame or key@first-last"),
+        (&["--left", "bogus"], "invalid --left 'bogus', expected a quirk preset, reference or reference:PRESET"),
+        (&["--right", "reference:bogus"], "invalid --right 'reference:bogus', expected a quirk preset, reference or reference:PRESET"),
+    ];
+    for (args, expected) in cases {
+        assert_eq!(parse_args(args), Err(expected.to_string()), "{:?}", args);
+    }
+}
+
+
+#[test]
+fn test_diff_sides() {
+    // reference takes --quirks wherever it is on the line
+    let parsed = parse_args(&["diff", "pong.ch8", "--left", "reference", "--right", "chip48", "--quirks", "schip"]);
+    let options = match parsed {
+        Ok(Command::Diff(options)) => options,
+        other => panic!("{:?}", other),
+    };
+    assert_eq!(options.left, Some(Side::Reference(SUPERCHIP)));
+    assert_eq!(options.right, Some(Side::Emulator(CHIP48)));
+
+    let options = match parse_args(&["diff", "pong.ch8", "--left", "reference:chip48", "--right", "reference", "--input", "5@3-9"]) {
+        Ok(Command::Diff(options)) => options,
+        other => panic!("{:?}", other),
+    };
+    assert_eq!(options.left, Some(Side::Reference(CHIP48)));
+    assert_eq!(options.right, Some(Side::Reference(COWGOD)));
+    assert_eq!(options.input, vec!["5@3-9".to_string()]);
+}
@@ -0,0 +1,249 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+
+// key bindings: chip-8 key as a hex digit ("0".."f") -> SDL scancode names.
+// scancodes are positional, so the default layout lands on the same
+// physical keys on AZERTY or Dvorak keyboards.
+pub type KeyBindings = BTreeMap<String, Vec<String>>;
+
+const MAX_RECENT: usize = 10;
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Config {
+    // layered over the default layout: listed chip-8 keys get the given
+    // host keys instead of their defaults, an empty list unbinds a key
+    pub keys: KeyBindings,
+    // same for controllers, with SDL mapping names: buttons as "a",
+    // "dpup" ..., axis directions as "leftx-", "lefty+" ...
+    pub controller: KeyBindings,
+    pub display: DisplayConfig,
+    pub window: WindowConfig,
+    pub sound: SoundConfig,
+    // directory holding the chip-8-database json files, by default
+    // "database" next to the config file
+    pub database: Option<PathBuf>,
+    // where save states go, by default "states" next to the config file
+    pub state_dir: Option<PathBuf>,
+    // frames fast-forward and turbo run per host frame; unset or 0 runs as
+    // many as fit in one
+    pub fast_forward: Option<u32>,
+    // where the launcher looks for roms, searching subdirectories too
+    pub rom_dir: Option<PathBuf>,
+    // roms started from the launcher, latest first; written back as they're played
+    pub recent: Vec<PathBuf>,
+    // per-rom overrides, keyed by the sha1 of the rom
+    pub roms: BTreeMap<String, RomConfig>,
+    // where this config was loaded from
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct DisplayConfig {
+    // amber, green, lcd, octo, high-contrast or colorblind
+    pub palette: Option<String>,
+    // "#rrggbb" colours replacing the palette's, background first
+    pub colors: Vec<String>,
+    // "fit", "integer" or "stretch"
+    pub scaling: Option<String>,
+    // against XOR flicker: "off", "fade" or "max2"
+    pub persistence: Option<String>,
+    // how many frames "fade" takes to fade a pixel out
+    pub fade_frames: Option<u32>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct SoundConfig {
+    // tone in Hz
+    pub frequency: Option<f32>,
+    // "square", "triangle", "sine" or "noise"
+    pub waveform: Option<String>,
+    // 0 to 100
+    pub volume: Option<f32>,
+    // fade in and out times in milliseconds, against clicks
+    pub attack_ms: Option<f32>,
+    pub release_ms: Option<f32>,
+    pub muted: bool,
+}
+
+// written back on exit, so the window comes back where it was left
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(default)]
+pub struct WindowConfig {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub x: Option<i32>,
+    pub y: Option<i32>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct RomConfig {
+    // these replace the global bindings of the chip-8 keys they list.
+    // games pick their own direction keys, so controllers usually need one.
+    pub keys: KeyBindings,
+    pub controller: KeyBindings,
+    // over the database's tickrate; --ipf still wins
+    pub instructions_per_frame: Option<u32>,
+}
+
+impl Config {
+    // $CHIPPY8_CONFIG, or chippy8/config.toml in the user's config directory
+    pub fn path() -> Option<PathBuf> {
+        if let Some(path) = env::var_os("CHIPPY8_CONFIG") {
+            return Some(PathBuf::from(path));
+        }
+        let config_home = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .or_else(|| env::var_os("APPDATA").map(PathBuf::from))?;
+        Some(config_home.join("chippy8").join("config.toml"))
+    }
+
+    // a missing config file is fine; a broken one is reported and ignored
+    pub fn load() -> Self {
+        match Config::path() {
+            Some(path) => Config::load_from(&path),
+            None => Config::default(),
+        }
+    }
+
+    pub fn load_from(path: &Path) -> Self {
+        let mut config = match fs::read_to_string(path) {
+            Err(_) => Config::default(),
+            Ok(text) => {
+                println!("loading config from {}", path.display());
+                toml::from_str(&text).unwrap_or_else(|e| {
+                    println!("ignoring invalid config {}: {}", path.display(), e);
+                    Config::default()
+                })
+            }
+        };
+        config.path = Some(path.to_path_buf());
+        config
+    }
+
+    pub fn database_dir(&self) -> Option<PathBuf> {
+        self.database.clone()
+            .or_else(|| Some(self.path.as_ref()?.parent()?.join("database")))
+    }
+
+    pub fn state_dir(&self) -> PathBuf {
+        self.state_dir.clone()
+            .or_else(|| Some(self.path.as_ref()?.parent()?.join("states")))
+            .unwrap_or_else(|| PathBuf::from("states"))
+    }
+
+    // changes the config file in place, keeping the rest of it as the user
+    // wrote it
+    fn edit(&self, change: impl FnOnce(&mut toml_edit::DocumentMut) -> Result<(), String>) -> Result<(), String> {
+        let path = self.path.clone().or_else(Config::path).ok_or("no config path")?;
+        let text = fs::read_to_string(&path).unwrap_or_default();
+        let mut document: toml_edit::DocumentMut = text.parse().map_err(|e| format!("{}: {}", path.display(), e))?;
+        change(&mut document).map_err(|e| format!("{}: {}", path.display(), e))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        }
+        fs::write(&path, document.to_string()).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    // updates just the [window] table
+    pub fn save_window(&self, window: WindowConfig) -> Result<(), String> {
+        self.edit(|document| {
+            let table = document.entry("window")
+                .or_insert_with(toml_edit::table)
+                .as_table_mut()
+                .ok_or("window is not a table")?;
+            let values = [
+                ("width", window.width.map(i64::from)),
+                ("height", window.height.map(i64::from)),
+                ("x", window.x.map(i64::from)),
+                ("y", window.y.map(i64::from)),
+            ];
+            for (key, value) in values.iter() {
+                if let Some(value) = value {
+                    table[key] = toml_edit::value(*value);
+                }
+            }
+            Ok(())
+        })
+    }
+
+    // moves the rom to the top of the recent list and saves the list
+    pub fn add_recent(&mut self, rom: &Path) -> Result<(), String> {
+        let rom = rom.canonicalize().unwrap_or_else(|_| rom.to_path_buf());
+        self.recent.retain(|path| *path != rom);
+        self.recent.insert(0, rom);
+        self.recent.truncate(MAX_RECENT);
+        let recent: toml_edit::Array = self.recent.iter()
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect();
+        self.edit(|document| {
+            document["recent"] = toml_edit::value(recent);
+            Ok(())
+        })
+    }
+
+    pub fn rom(&self, sha1: &str) -> Option<&RomConfig> {
+        self.roms.get(sha1)
+    }
+}
+
+/* default keyboard layout:
+   1 2 3 4      1 2 3 c
+   Q W E R  ->  4 5 6 d
+   A S D F      7 8 9 e
+   Z X C V      a 0 b f
+*/
+pub fn default_key_bindings() -> KeyBindings {
+    [
+        ("1", "1"), ("2", "2"), ("3", "3"), ("c", "4"),
+        ("4", "Q"), ("5", "W"), ("6", "E"), ("d", "R"),
+        ("7", "A"), ("8", "S"), ("9", "D"), ("e", "F"),
+        ("a", "Z"), ("0", "X"), ("b", "C"), ("f", "V"),
+    ].iter()
+        .map(|&(key, scancode)| (key.to_string(), vec![scancode.to_string()]))
+        .collect()
+}
+
+// d-pad and left stick on 5/7/8/9 (WASD in the default layout), which most
+// modern games use for directions, and the face buttons on 6 and 4
+pub fn default_controller_bindings() -> KeyBindings {
+    [
+        ("5", &["dpup", "lefty-"][..]),
+        ("8", &["dpdown", "lefty+"][..]),
+        ("7", &["dpleft", "leftx-"][..]),
+        ("9", &["dpright", "leftx+"][..]),
+        ("6", &["a"][..]),
+        ("4", &["b"][..]),
+    ].iter()
+        .map(|&(key, inputs)| (key.to_string(), inputs.iter().map(|input| input.to_string()).collect()))
+        .collect()
+}
+
+// the chip-8-database lists which keys a game uses for directions and
+// buttons; put them on the d-pad, left stick and face buttons
+pub fn controller_bindings_from_hints(hints: &BTreeMap<String, u8>) -> KeyBindings {
+    let mut bindings = KeyBindings::new();
+    for (hint, &key) in hints {
+        let inputs: &[&str] = match hint.as_str() {
+            "up" => &["dpup", "lefty-"],
+            "down" => &["dpdown", "lefty+"],
+            "left" => &["dpleft", "leftx-"],
+            "right" => &["dpright", "leftx+"],
+            "a" => &["a"],
+            "b" => &["b"],
+            _ => continue,
+        };
+        bindings.entry(format!("{:x}", key))
+            .or_default()
+            .extend(inputs.iter().map(|input| input.to_string()));
+    }
+    bindings
+}
@@ -1,14 +1,40 @@
 // reference: http://devernay.free.fr/hacks/chip8/C8TECH10.HTM
-use rand::Rng;
-use std::cmp;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
 use crate::fonts::*;
-use crate::cartridge::MAX_ROM_SIZE;
-
-const RESET_VECTOR: u16 = 0x200;
-const RAM_LENGTH: usize = 0x1000;
+use crate::cartridge::CartridgeError;
+use crate::lockstep::Snapshot;
+use crate::quirks::Quirks;
+
+pub const RESET_VECTOR: u16 = 0x200;
+pub const RAM_LENGTH: usize = 0x1000;
+// addresses are 12 bits, so pc, I and anything read or written through
+// them wrap around the end of ram
+const ADDRESS_MASK: u16 = 0xFFF;
 const OPCODE_SIZE: u16 = 2;
+pub const DEFAULT_INSTRUCTIONS_PER_FRAME: u32 = 10;
+pub const MAX_INSTRUCTIONS_PER_FRAME: u32 = 100_000;
+// COSMAC VIP timing, in 1802 machine cycles (8 clocks at 1.7609 MHz).
+// a 60 Hz frame is 3668 machine cycles, of which the display interrupt
+// routine and the DMA of 128 scanlines take about half.
+const VIP_CYCLES_PER_FRAME: i32 = 3668;
+const VIP_DISPLAY_INTERRUPT_CYCLES: i32 = 1832;
+// the interpreter's fetch/decode loop, charged on top of every instruction
+const VIP_FETCH_CYCLES: i32 = 68;
+// extra cost of a taken skip
+const VIP_SKIP_CYCLES: i32 = 4;
 pub const DISPLAY_HEIGHT: usize = 32;
 pub const DISPLAY_WIDTH: usize = 64;
+// save states start with a magic number and a version, so old or foreign
+// files are refused instead of loaded as garbage
+const STATE_MAGIC: &[u8; 4] = b"C8ST";
+const STATE_VERSION: u8 = 1;
+const STATE_LENGTH: usize = 4 + 1 // magic, version
+    + 2 + 16 + 1 + 16 * 2 + 2 // pc, v, sp, stack, i
+    + 1 + 1 // timers
+    + RAM_LENGTH + DISPLAY_WIDTH * DISPLAY_HEIGHT
+    + 1 + 1 // awaiting_keypress, first_key_pressed_register
+    + 8 + 4; // instruction_count, vip_cycles_left
 
 enum InstructionPointer {
     Inc, // just run the next instruction
@@ -16,6 +42,15 @@ enum InstructionPointer {
     Skip, // skip one PC instruction
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimingMode {
+    // run a flat instructions_per_frame every frame
+    InstructionsPerFrame,
+    // charge each instruction the machine cycles it took on the COSMAC VIP
+    // interpreter, and make DRW wait for the display interrupt
+    CosmacVip,
+}
+
 pub struct Output<'a> {
     pub vram: &'a [[u8; DISPLAY_WIDTH]; DISPLAY_HEIGHT],
     pub vram_changed: bool,
@@ -30,12 +65,26 @@ pub struct Cpu {
     i: u16,
     delay_timer: u8,
     sound_timer: u8,
-    ram: [u8; 0x1000],
+    ram: [u8; RAM_LENGTH],
     vram: [[u8; DISPLAY_WIDTH]; DISPLAY_HEIGHT],
     awaiting_keypress: bool,
     first_key_pressed_register: usize,
     keys_pressed: [bool; 16],
     vram_changed: bool,
+    instructions_per_frame: u32,
+    timing_mode: TimingMode,
+    quirks: Quirks,
+    rng: StdRng,
+    instruction_count: u64,
+    // machine cycles left in the current frame; negative when the last
+    // instruction ran past the display interrupt
+    vip_cycles_left: i32,
+}
+
+impl Default for Cpu {
+    fn default() -> Self {
+        Cpu::new()
+    }
 }
 
 impl Cpu {
@@ -51,34 +100,214 @@ impl Cpu {
             i: 0,
             delay_timer: 0,
             sound_timer: 0,
-            ram: ram,
+            ram,
             vram: [[0; DISPLAY_WIDTH]; DISPLAY_HEIGHT],
             awaiting_keypress: false,
             first_key_pressed_register: 0,
             keys_pressed: [false; 16],
             vram_changed: false,
+            instructions_per_frame: DEFAULT_INSTRUCTIONS_PER_FRAME,
+            timing_mode: TimingMode::InstructionsPerFrame,
+            quirks: Quirks::default(),
+            rng: StdRng::from_entropy(),
+            instruction_count: 0,
+            vip_cycles_left: 0,
         }
     }
 
-    pub fn load_rom(&mut self, rom: &Vec<u8>) {
-        for i in 0..cmp::min(rom.len(), MAX_ROM_SIZE) {
-            self.ram[RESET_VECTOR as usize + i] = rom[i]
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), CartridgeError> {
+        let start = RESET_VECTOR as usize;
+        let max = RAM_LENGTH - start;
+        if rom.len() > max {
+            return Err(CartridgeError::TooLarge { size: rom.len(), max, platform: None });
+        }
+        self.ram[start..start + rom.len()].copy_from_slice(rom);
+        Ok(())
+    }
+
+    // a new build of the program over the old one, clearing the rest of the
+    // old one where it was longer
+    pub fn replace_program(&mut self, rom: &[u8], old_length: usize) -> Result<(), CartridgeError> {
+        self.load_rom(rom)?;
+        let start = RESET_VECTOR as usize;
+        let old_end = (start + old_length).min(RAM_LENGTH);
+        if start + rom.len() < old_end {
+            self.ram[start + rom.len()..old_end].fill(0);
         }
+        Ok(())
     }
 
-    pub fn tick_60_hz(&mut self, keys_pressed: &[bool; 16]) -> Output {
-        let mut vram_changed_in_frame = false;
-        for _ in 0..10 {
-            self.tick(keys_pressed);
-            vram_changed_in_frame |= self.vram_changed;
+    pub fn instructions_per_frame(&self) -> u32 {
+        self.instructions_per_frame
+    }
+
+    // how many instructions tick_60_hz runs between timer decrements.
+    // 10 per frame (600 IPS) suits most CHIP-8 games; SCHIP/XO-CHIP games
+    // usually want a lot more.
+    pub fn set_instructions_per_frame(&mut self, instructions_per_frame: u32) {
+        self.instructions_per_frame = instructions_per_frame.clamp(1, MAX_INSTRUCTIONS_PER_FRAME);
+    }
+
+    pub fn set_timing_mode(&mut self, timing_mode: TimingMode) {
+        self.timing_mode = timing_mode;
+        self.vip_cycles_left = 0;
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    // makes RND repeatable
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    // instructions executed since reset
+    pub fn instruction_count(&self) -> u64 {
+        self.instruction_count
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn i(&self) -> u16 {
+        self.i
+    }
+
+    pub fn sp(&self) -> u8 {
+        self.sp
+    }
+
+    pub fn v(&self) -> &[u8; 16] {
+        &self.v
+    }
+
+    pub fn vram(&self) -> &[[u8; DISPLAY_WIDTH]; DISPLAY_HEIGHT] {
+        &self.vram
+    }
+
+    // stopped at LD Vx, K until a key goes down
+    pub fn is_awaiting_keypress(&self) -> bool {
+        self.awaiting_keypress
+    }
+
+    // everything a running program can change. the speed, quirks and
+    // random number generator are settings, so they stay as they are when
+    // a state is loaded.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = Vec::with_capacity(STATE_LENGTH);
+        state.extend_from_slice(STATE_MAGIC);
+        state.push(STATE_VERSION);
+        state.extend_from_slice(&self.pc.to_be_bytes());
+        state.extend_from_slice(&self.v);
+        state.push(self.sp);
+        for addr in self.stack.iter() {
+            state.extend_from_slice(&addr.to_be_bytes());
         }
-        if self.delay_timer > 0 {
-            self.delay_timer -= 1;
+        state.extend_from_slice(&self.i.to_be_bytes());
+        state.push(self.delay_timer);
+        state.push(self.sound_timer);
+        state.extend_from_slice(&self.ram);
+        for row in self.vram.iter() {
+            state.extend_from_slice(row);
         }
+        state.push(self.awaiting_keypress as u8);
+        state.push(self.first_key_pressed_register as u8);
+        state.extend_from_slice(&self.instruction_count.to_be_bytes());
+        state.extend_from_slice(&self.vip_cycles_left.to_be_bytes());
+        state
+    }
 
-        if self.sound_timer > 0 {
-            self.sound_timer -= 1;
+    // the cpu is left untouched when the state is refused
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), String> {
+        if state.len() != STATE_LENGTH || &state[..4] != STATE_MAGIC {
+            return Err("not a chippy8 save state".to_string());
+        }
+        if state[4] != STATE_VERSION {
+            return Err(format!("save state version {} is not supported", state[4]));
+        }
+        let mut rest = &state[5..];
+        let mut take = |length: usize| {
+            let (taken, remaining) = rest.split_at(length);
+            rest = remaining;
+            taken
+        };
+        let word = |bytes: &[u8]| u16::from_be_bytes([bytes[0], bytes[1]]);
+        let pc = word(take(2));
+        let mut v = [0; 16];
+        v.copy_from_slice(take(16));
+        let sp = take(1)[0];
+        let mut stack = [0; 16];
+        for addr in stack.iter_mut() {
+            *addr = word(take(2));
+        }
+        let i = word(take(2));
+        let delay_timer = take(1)[0];
+        let sound_timer = take(1)[0];
+        let ram = take(RAM_LENGTH);
+        let vram = take(DISPLAY_WIDTH * DISPLAY_HEIGHT);
+        let awaiting_keypress = take(1)[0] != 0;
+        let first_key_pressed_register = take(1)[0] as usize;
+        let mut count = [0; 8];
+        count.copy_from_slice(take(8));
+        let mut cycles = [0; 4];
+        cycles.copy_from_slice(take(4));
+        if pc > ADDRESS_MASK || i > ADDRESS_MASK || sp as usize >= stack.len() ||
+            first_key_pressed_register >= v.len() {
+            return Err("save state is corrupt".to_string());
+        }
+        self.pc = pc;
+        self.v = v;
+        self.sp = sp;
+        self.stack = stack;
+        self.i = i;
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        self.ram.copy_from_slice(ram);
+        for (row, saved) in self.vram.iter_mut().zip(vram.chunks(DISPLAY_WIDTH)) {
+            row.copy_from_slice(saved);
+        }
+        self.awaiting_keypress = awaiting_keypress;
+        self.first_key_pressed_register = first_key_pressed_register;
+        self.instruction_count = u64::from_be_bytes(count);
+        self.vip_cycles_left = i32::from_be_bytes(cycles);
+        self.vram_changed = true;
+        Ok(())
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            pc: self.pc,
+            v: self.v,
+            i: self.i,
+            sp: self.sp,
+            stack: self.stack,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            waiting: if self.awaiting_keypress { Some(self.first_key_pressed_register as u8) } else { None },
+            ram: self.ram,
+            vram: self.vram,
+        }
+    }
+
+    // takes over another cpu's ram apart from the program area, so data a
+    // game left in memory survives loading a new build of it
+    pub fn keep_ram(&mut self, other: &Cpu, program_length: usize) {
+        let program = RESET_VECTOR as usize..RESET_VECTOR as usize + program_length;
+        for (addr, byte) in self.ram.iter_mut().enumerate() {
+            if !program.contains(&addr) {
+                *byte = other.ram[addr];
+            }
         }
+    }
+
+    pub fn tick_60_hz(&mut self, keys_pressed: &[bool; 16]) -> Output<'_> {
+        let vram_changed_in_frame = match self.timing_mode {
+            TimingMode::InstructionsPerFrame => self.run_instructions(keys_pressed),
+            TimingMode::CosmacVip => self.run_vip_cycles(keys_pressed),
+        };
+        self.tick_timers();
         Output {
             vram: &self.vram,
             vram_changed: vram_changed_in_frame,
@@ -86,18 +315,115 @@ impl Cpu {
         }
     }
 
+    // the 60 Hz interrupt
+    pub fn tick_timers(&mut self) {
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1;
+        }
 
-    pub fn tick(&mut self, keys_pressed: &[bool; 16]) -> Output {
+        if self.sound_timer > 0 {
+            self.sound_timer -= 1;
+        }
+    }
+
+
+    // returns whether any instruction in the frame touched vram
+    fn run_instructions(&mut self, keys_pressed: &[bool; 16]) -> bool {
+        let mut vram_changed_in_frame = false;
+        for _ in 0..self.instructions_per_frame {
+            self.tick(keys_pressed);
+            vram_changed_in_frame |= self.vram_changed;
+            // only DRW changes vram; with the vblank quirk it idles until
+            // the next interrupt
+            if self.quirks.vblank && self.vram_changed {
+                break;
+            }
+        }
+        vram_changed_in_frame
+    }
+
+    // runs instructions until this frame's share of VIP machine cycles is
+    // used up. returns whether any instruction in the frame touched vram
+    fn run_vip_cycles(&mut self, keys_pressed: &[bool; 16]) -> bool {
+        let mut vram_changed_in_frame = false;
+        let mut first_in_frame = true;
+        self.vip_cycles_left += VIP_CYCLES_PER_FRAME - VIP_DISPLAY_INTERRUPT_CYCLES;
+        while self.vip_cycles_left > 0 {
+            self.poll_keypress(keys_pressed);
+            let op = self.fetch();
+            // waiting for a key or for the display interrupt idles away
+            // the rest of the frame
+            let waiting_for_interrupt = op & 0xF000 == 0xD000 && !first_in_frame;
+            if self.awaiting_keypress || waiting_for_interrupt {
+                self.vip_cycles_left = 0;
+                break;
+            }
+            let mut cycles = self.vip_cycles(op);
+            let pc = self.pc;
+            self.vram_changed = false;
+            self.execute(op);
+            vram_changed_in_frame |= self.vram_changed;
+            if is_skip(op) && self.pc == (pc + OPCODE_SIZE * 2) & ADDRESS_MASK {
+                cycles += VIP_SKIP_CYCLES;
+            }
+            self.vip_cycles_left -= cycles;
+            first_in_frame = false;
+        }
+        vram_changed_in_frame
+    }
+
+    // machine cycles the VIP interpreter spends on an opcode, not counting
+    // a taken skip. approximate, after published measurements of the
+    // original interpreter.
+    fn vip_cycles(&self, opcode: u16) -> i32 {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let n = (opcode & 0x000F) as i32;
+        let cycles = match opcode >> 12 {
+            0x0 if opcode == 0x00E0 => 24,
+            0x0 => 10,
+            0x1 => 12,
+            0x2 => 26,
+            0x3 | 0x4 => 10,
+            0x5 | 0x9 => 14,
+            0x6 => 6,
+            0x7 => 10,
+            0x8 => 44,
+            0xa => 12,
+            0xb => 22,
+            0xc => 36,
+            0xd => {
+                // a sprite that isn't byte aligned touches two framebuffer
+                // bytes per row
+                let row_cycles = if self.v[x] & 0x7 == 0 { 18 } else { 34 };
+                26 + n * row_cycles
+            }
+            0xe => 14,
+            _ => match opcode & 0x00FF {
+                0x1e | 0x29 => 16,
+                // BCD is done by repeated subtraction
+                0x33 => {
+                    let vx = self.v[x] as i32;
+                    84 + 16 * (vx / 100 + vx / 10 % 10 + vx % 10)
+                }
+                0x55 | 0x65 => 14 + 14 * (x as i32 + 1),
+                _ => 10,
+            },
+        };
+        VIP_FETCH_CYCLES + cycles
+    }
+
+    fn poll_keypress(&mut self, keys_pressed: &[bool; 16]) {
         self.keys_pressed = *keys_pressed;
         if self.awaiting_keypress {
-            for i in 0..keys_pressed.len() {
-                if keys_pressed[i] {
-                    self.v[self.first_key_pressed_register] = i as u8;
-                    self.awaiting_keypress = false;
-                    break;
-                }
+            if let Some(key) = keys_pressed.iter().position(|&pressed| pressed) {
+                self.v[self.first_key_pressed_register] = key as u8;
+                self.awaiting_keypress = false;
             }
         }
+    }
+
+    pub fn tick(&mut self, keys_pressed: &[bool; 16]) -> Output<'_> {
+        self.poll_keypress(keys_pressed);
         self.vram_changed = false;
         if !self.awaiting_keypress {
             let op = self.fetch();
@@ -111,9 +437,7 @@ impl Cpu {
     }
 
     pub fn fetch(&self) -> u16 {
-        let addr = self.pc as usize;
-        let opcode = (self.ram[addr] as u16) << 8 | (self.ram[addr+1] as u16);
-        opcode
+        (self.ram[ram_addr(self.pc as usize)] as u16) << 8 | (self.ram[ram_addr(self.pc as usize + 1)] as u16)
     }
 
     pub fn execute(&mut self, opcode: u16) {
@@ -123,7 +447,7 @@ impl Cpu {
             ((opcode & 0x00F0) >> 4) as u8,
             (opcode & 0x000F) as u8
         );
-        let nnn = (opcode & 0x0FFF) as u16;
+        let nnn = opcode & 0x0FFF;
         let kk =  (opcode & 0x00FF) as u8;
         let x =  ((opcode & 0x0F00) >> 8) as usize;
         let y =  ((opcode & 0x00F0) >> 4) as usize;
@@ -165,13 +489,17 @@ impl Cpu {
             (0xf,   _, 0x6, 0x5) => self.op_ld_vx_i(x),
             (0xf,   _,   _, 0xe) => self.op_add_i_vx(x),
 
-            _ => panic!(format!("Unrecognized opcode: {:X}", opcode)),
+            // 0nnn machine code routines, and opcodes no interpreter has,
+            // do nothing
+            _ => InstructionPointer::Inc,
         };
-        match next_ip {
-            InstructionPointer::Inc => self.pc += OPCODE_SIZE,
-            InstructionPointer::Jump(addr) => self.pc = addr,
-            InstructionPointer::Skip => self.pc += OPCODE_SIZE * 2,
-        }
+        self.instruction_count += 1;
+        let pc = match next_ip {
+            InstructionPointer::Inc => self.pc.wrapping_add(OPCODE_SIZE),
+            InstructionPointer::Jump(addr) => addr,
+            InstructionPointer::Skip => self.pc.wrapping_add(OPCODE_SIZE * 2),
+        };
+        self.pc = pc & ADDRESS_MASK;
     }
 
     /*
@@ -189,9 +517,12 @@ impl Cpu {
 
     // 00EE - RET - Return from a subroutine.
     // The interpreter sets the program counter to the address at the top of the stack, then subtracts 1 from the stack pointer.
+    // The stack has 16 entries and wraps around, so a RET with nothing to
+    // return to, or a call nested too deep, loses return addresses rather
+    // than stopping the program.
     fn op_ret(&mut self) -> InstructionPointer {
         let next_pc = self.stack[self.sp as usize];
-        self.sp -= 1;
+        self.sp = self.sp.wrapping_sub(1) % self.stack.len() as u8;
         InstructionPointer::Jump(next_pc)
     }
 
@@ -206,8 +537,8 @@ impl Cpu {
     // then puts the current PC on the top of the stack. 
     // The PC is then set to nnn.
     fn op_call(&mut self, nnn: u16) -> InstructionPointer {
-        self.sp += 1;
-        self.stack[self.sp as usize] = self.pc + OPCODE_SIZE;
+        self.sp = (self.sp + 1) % self.stack.len() as u8;
+        self.stack[self.sp as usize] = (self.pc + OPCODE_SIZE) & ADDRESS_MASK;
         InstructionPointer::Jump(nnn)
     }
 
@@ -267,7 +598,10 @@ impl Cpu {
 
     // 8xy1 - OR Vx, Vy. Set Vx = Vx OR Vy.
     fn op_or(&mut self, x: usize, y: usize) -> InstructionPointer {
-        self.v[x] = self.v[x] | self.v[y];
+        self.v[x] |= self.v[y];
+        if self.quirks.logic {
+            self.v[0xf] = 0;
+        }
         InstructionPointer::Inc
     }
 
@@ -275,14 +609,20 @@ impl Cpu {
     // 8xy2 - AND Vx, Vy
     // Set Vx = Vx AND Vy.
     fn op_and(&mut self, x: usize, y: usize) -> InstructionPointer {
-        self.v[x] = self.v[x] & self.v[y];
+        self.v[x] &= self.v[y];
+        if self.quirks.logic {
+            self.v[0xf] = 0;
+        }
         InstructionPointer::Inc
     }
  
 
     // 8xy3 - XOR Vx, Vy - Set Vx = Vx XOR Vy.
     fn op_xor(&mut self, x: usize, y: usize) -> InstructionPointer {
-        self.v[x] = self.v[x] ^ self.v[y];
+        self.v[x] ^= self.v[y];
+        if self.quirks.logic {
+            self.v[0xf] = 0;
+        }
         InstructionPointer::Inc
     }
  
@@ -310,9 +650,11 @@ impl Cpu {
 
     // 8xy6 - SHR Vx {, Vy} - Set Vx = Vx SHR 1.
     // If the least-significant bit of Vx is 1, then VF is set to 1, otherwise 0. Then Vx is divided by 2.
-    fn op_shr(&mut self, x: usize, _: usize) -> InstructionPointer {
-        self.v[0xf] = if self.v[x] & 0x01 != 0 { 1 } else { 0 };
-        self.v[x] = self.v[x] >> 1;
+    // Without the shift quirk Vy is shifted into Vx, as on the COSMAC VIP.
+    fn op_shr(&mut self, x: usize, y: usize) -> InstructionPointer {
+        let value = if self.quirks.shift { self.v[x] } else { self.v[y] };
+        self.v[x] = value >> 1;
+        self.v[0xf] = value & 0x01;
         InstructionPointer::Inc
     }
     
@@ -329,9 +671,11 @@ impl Cpu {
 
     // 8xyE - SHL Vx {, Vy} - Set Vx = Vx SHL 1.
     // If the most-significant bit of Vx is 1, then VF is set to 1, otherwise to 0. Then Vx is multiplied by 2.
-    fn op_shl(&mut self, x: usize, _y: usize) -> InstructionPointer {
-        self.v[0xf] = if self.v[x] & 0x80 != 0 { 1 } else { 0 };
-        self.v[x] = self.v[x] << 1;
+    // Without the shift quirk Vy is shifted into Vx, as on the COSMAC VIP.
+    fn op_shl(&mut self, x: usize, y: usize) -> InstructionPointer {
+        let value = if self.quirks.shift { self.v[x] } else { self.v[y] };
+        self.v[x] = value << 1;
+        self.v[0xf] = value >> 7;
         InstructionPointer::Inc
     }
 
@@ -357,14 +701,16 @@ impl Cpu {
 
     // Bnnn - JP V0, addr - Jump to location nnn + V0.
     // The program counter is set to nnn plus the value of V0.
+    // With the jump quirk it's Bxnn - jump to xnn plus Vx, as on SCHIP.
     fn op_jpv(&mut self, nnn: u16) -> InstructionPointer {
-        InstructionPointer::Jump(nnn + self.v[0] as u16)
+        let x = if self.quirks.jump { (nnn >> 8) as usize } else { 0 };
+        InstructionPointer::Jump(nnn + self.v[x] as u16)
     }
 
     // Cxkk - RND Vx, byte - Set Vx = random byte AND kk.
     // The interpreter generates a random number from 0 to 255, which is then ANDed with the value kk. The results are stored in Vx. See instruction 8xy2 for more information on AND.
     fn op_rnd(&mut self, x: usize, kk: u8) -> InstructionPointer {
-        let r: u8 = rand::thread_rng().gen();
+        let r: u8 = self.rng.gen();
         self.v[x] = kk & r;
         InstructionPointer::Inc
     }
@@ -381,6 +727,8 @@ impl Cpu {
     // If the sprite is positioned so part of it is outside 
     // the coordinates of the display, it wraps around to 
     // the opposite side of the screen. 
+    // Without the wrap quirk the start position still wraps,
+    // but the part of the sprite past the edges is clipped.
     //
     // vram should be laid out as a 64x32 monochrome pixel display
     //               x tracks columns
@@ -393,11 +741,19 @@ impl Cpu {
     //  +--------------------------------------------+
     fn op_drw(&mut self, x: usize, y: usize, n: usize) -> InstructionPointer {
         self.v[0xf] = 0;
+        let start_row = self.v[y] as usize % DISPLAY_HEIGHT;
+        let start_column = self.v[x] as usize % DISPLAY_WIDTH;
         for byte in 0..n {
-            let row = (self.v[y] as usize + byte) % DISPLAY_HEIGHT;
-            let pixel_byte = self.ram[self.i as usize + byte];
+            if !self.quirks.wrap && start_row + byte >= DISPLAY_HEIGHT {
+                break;
+            }
+            let row = (start_row + byte) % DISPLAY_HEIGHT;
+            let pixel_byte = self.ram[ram_addr(self.i as usize + byte)];
             for bit in 0..8 {
-                let column = (self.v[x] as usize + bit) % DISPLAY_WIDTH;
+                if !self.quirks.wrap && start_column + bit >= DISPLAY_WIDTH {
+                    break;
+                }
+                let column = (start_column + bit) % DISPLAY_WIDTH;
                 let pixel_data = (pixel_byte >> (7 - bit)) & 0x1;
                 let current_data = self.vram[row][column];
                 if (current_data & pixel_data) != 0 {
@@ -415,7 +771,8 @@ impl Cpu {
     // Ex9E - SKP Vx - Skip next instruction if key with the value of Vx is pressed.
     // Checks the keyboard, and if the key corresponding to the value of Vx is currently in the down position, PC is increased by 2.
     fn op_skp(&mut self, x: usize) -> InstructionPointer {
-        if self.keys_pressed[self.v[x] as usize] {
+        // only the low nibble picks the key
+        if self.keys_pressed[self.v[x] as usize & 0xF] {
             InstructionPointer::Skip
         } else {
             InstructionPointer::Inc
@@ -425,7 +782,7 @@ impl Cpu {
     // ExA1 - SKNP Vx - Skip next instruction if key with the value of Vx is not pressed.
     // Checks the keyboard, and if the key corresponding to the value of Vx is currently in the up position, PC is increased by 2.
     fn op_sknp(&mut self, x: usize) -> InstructionPointer {
-        if !self.keys_pressed[self.v[x] as usize] {
+        if !self.keys_pressed[self.v[x] as usize & 0xF] {
             InstructionPointer::Skip
         } else {
             InstructionPointer::Inc
@@ -463,7 +820,7 @@ impl Cpu {
     // Fx1E - ADD I, Vx - Set I = I + Vx.
     // The values of I and Vx are added, and the results are stored in I.
     fn op_add_i_vx(&mut self, x: usize) -> InstructionPointer {
-        self.i += self.v[x] as u16;
+        self.i = (self.i + self.v[x] as u16) & ADDRESS_MASK;
         InstructionPointer::Inc
     }
 
@@ -481,9 +838,9 @@ impl Cpu {
         let hundreds = self.v[x] / 100;
         let tens = (self.v[x] % 100) / 10;
         let ones = self.v[x] % 10;
-        self.ram[self.i as usize] = hundreds;
-        self.ram[self.i as usize + 1] = tens;
-        self.ram[self.i as usize + 2] = ones;
+        self.ram[ram_addr(self.i as usize)] = hundreds;
+        self.ram[ram_addr(self.i as usize + 1)] = tens;
+        self.ram[ram_addr(self.i as usize + 2)] = ones;
         InstructionPointer::Inc
     }
 
@@ -492,8 +849,9 @@ impl Cpu {
     // The interpreter copies the values of registers V0 through Vx into memory, starting at the address in I.
     fn op_ld_i_vx(&mut self, x: usize) -> InstructionPointer {
         for i in 0..x+1 {
-            self.ram[self.i as usize + i] = self.v[i];
+            self.ram[ram_addr(self.i as usize + i)] = self.v[i];
         }
+        self.advance_i_after_load_store(x);
         InstructionPointer::Inc
     }
 
@@ -502,13 +860,32 @@ impl Cpu {
     // The interpreter reads values from memory starting at location I into registers V0 through Vx.
     fn op_ld_vx_i(&mut self, x: usize) -> InstructionPointer {
         for i in 0..x+1 {
-            self.v[i] = self.ram[self.i as usize + i];
+            self.v[i] = self.ram[ram_addr(self.i as usize + i)];
         }
+        self.advance_i_after_load_store(x);
         InstructionPointer::Inc
     }
+
+    // the COSMAC VIP leaves I pointing past the last register transferred
+    fn advance_i_after_load_store(&mut self, x: usize) {
+        if self.quirks.memory_leave_i_unchanged {
+            return;
+        }
+        let increment = if self.quirks.memory_increment_by_x { x } else { x + 1 };
+        self.i = (self.i + increment as u16) & ADDRESS_MASK;
+    }
  
 }
 
+fn ram_addr(addr: usize) -> usize {
+    addr & ADDRESS_MASK as usize
+}
+
+// 3xkk, 4xkk, 5xy0, 9xy0, Ex9E and ExA1
+fn is_skip(opcode: u16) -> bool {
+    matches!(opcode >> 12, 0x3 | 0x4 | 0x5 | 0x9 | 0xe)
+}
+
 #[cfg(test)]
 #[path = "./cpu_tests.rs"]
 mod cpu_tests;
@@ -24,6 +24,81 @@ fn test_init() {
     assert_eq!(cpu.ram[1 + HEX_DIGIT_DATA.len() - HEX_DIGIT_BYTE_LENGTH], 0x80); // first byte of f
 }
 
+#[test]
+fn test_load_rom() {
+    let mut cpu = Cpu::new();
+    assert!(cpu.load_rom(&[0x12, 0x00]).is_ok());
+    assert_eq!(cpu.ram[0x200], 0x12);
+    assert_eq!(cpu.ram[0x201], 0x00);
+    // fills memory right up to the end
+    assert!(cpu.load_rom(&[0xAA; 0xE00]).is_ok());
+    assert_eq!(cpu.ram[0xFFF], 0xAA);
+    // one byte more is refused rather than truncated
+    let mut cpu = Cpu::new();
+    assert!(cpu.load_rom(&[0xAA; 0xE01]).is_err());
+    assert_eq!(cpu.ram[0x200], 0x00);
+}
+
+#[test]
+fn test_save_state() {
+    let mut cpu = setup_cpu();
+    cpu.sp = 2;
+    cpu.stack[1] = 0x345;
+    cpu.i = 0x678;
+    cpu.delay_timer = 9;
+    cpu.ram[0xFFF] = 0x55;
+    cpu.vram[31][63] = 1;
+    let state = cpu.save_state();
+    let mut restored = Cpu::new();
+    assert!(restored.load_state(&state).is_ok());
+    assert_eq!(restored.pc, PC);
+    assert_eq!(restored.v, cpu.v);
+    assert_eq!(restored.sp, 2);
+    assert_eq!(restored.stack[1], 0x345);
+    assert_eq!(restored.i, 0x678);
+    assert_eq!(restored.delay_timer, 9);
+    assert_eq!(restored.ram[0xFFF], 0x55);
+    assert_eq!(restored.vram[31][63], 1);
+    assert_eq!(restored.save_state(), state);
+    // anything else is refused without touching the cpu
+    let mut cpu = Cpu::new();
+    assert!(cpu.load_state(&state[1..]).is_err());
+    let mut corrupt = state.clone();
+    corrupt[5 + 2 + 16] = 17;
+    assert!(cpu.load_state(&corrupt).is_err());
+    assert_eq!(cpu.pc, 0x200);
+}
+
+#[test]
+fn test_keep_ram() {
+    let mut old = Cpu::new();
+    old.ram[0x1FF] = 1;
+    old.ram[0x200] = 2;
+    old.ram[0x203] = 3;
+    old.ram[0x204] = 4;
+    let mut cpu = Cpu::new();
+    cpu.load_rom(&[0xAA; 4]).unwrap();
+    cpu.keep_ram(&old, 4);
+    assert_eq!(cpu.ram[0x1FF], 1);
+    assert_eq!(cpu.ram[0x200], 0xAA);
+    assert_eq!(cpu.ram[0x203], 0xAA);
+    assert_eq!(cpu.ram[0x204], 4);
+}
+
+#[test]
+fn test_replace_program() {
+    let mut cpu = Cpu::new();
+    cpu.load_rom(&[0xBB; 6]).unwrap();
+    cpu.ram[0x206] = 6;
+    // a shorter build leaves none of the old one behind it
+    cpu.replace_program(&[0xAA; 2], 6).unwrap();
+    assert_eq!(&cpu.ram[0x200..0x207], &[0xAA, 0xAA, 0, 0, 0, 0, 6]);
+    // a longer one just goes over it
+    cpu.replace_program(&[0xCC; 8], 2).unwrap();
+    assert_eq!(&cpu.ram[0x200..0x209], &[0xCC, 0xCC, 0xCC, 0xCC, 0xCC, 0xCC, 0xCC, 0xCC, 0]);
+    assert!(cpu.replace_program(&[0; 0x1000], 0).is_err());
+}
+
 #[test]
 fn test_cls() {
     // 00E0 - CLS
@@ -41,19 +116,61 @@ fn test_ret() {
     // The interpreter sets the program counter to the address at the top of the stack, then subtracts 1 from the stack pointer.
     let mut cpu = setup_cpu();
     cpu.sp += 1;
-    let final_cpu: u16 = 0x1234;
+    let final_cpu: u16 = 0x234;
     cpu.stack[cpu.sp as usize] = final_cpu;
     cpu.execute(0x00EE);
     assert_eq!(cpu.pc, final_cpu);
     assert_eq!(cpu.sp, 0);
     cpu.sp = 15;
-    let final_cpu2: u16 = 0xaced;
+    let final_cpu2: u16 = 0xced;
     cpu.stack[cpu.sp as usize] = final_cpu2;
     cpu.execute(0x00EE);
     assert_eq!(cpu.pc, final_cpu2);
     assert_eq!(cpu.sp, 14);
 }
 
+#[test]
+fn test_stack_wraps() {
+    // a RET with nothing to return to, or a 17th nested CALL, wraps around
+    // the 16 entries instead of leaving the stack
+    let mut cpu = setup_cpu();
+    cpu.stack[0] = 0x300;
+    cpu.execute(0x00EE);
+    assert_eq!(cpu.pc, 0x300);
+    assert_eq!(cpu.sp, 15);
+    cpu.execute(0x2400);
+    assert_eq!(cpu.sp, 0);
+    assert_eq!(cpu.stack[0], 0x302);
+}
+
+#[test]
+fn test_addresses_wrap() {
+    // pc, I and memory accesses through I stay within 4K
+    let mut cpu = setup_cpu();
+    cpu.v[0] = 0xFF;
+    cpu.execute(0xBFFF);
+    assert_eq!(cpu.pc, 0x0FE);
+    cpu.i = 0xFFE;
+    cpu.execute(0xF01E);
+    assert_eq!(cpu.i, 0x0FD);
+    cpu.i = 0xFFF;
+    cpu.v[1] = 0xAB;
+    cpu.execute(0xF155);
+    assert_eq!(cpu.ram[0xFFF], 0xFF);
+    assert_eq!(cpu.ram[0x000], 0xAB);
+    // keys are picked by the low nibble of Vx
+    cpu.pc = PC;
+    cpu.v[2] = 0x35;
+    cpu.keys_pressed[5] = true;
+    cpu.execute(0xE29E);
+    assert_eq!(cpu.pc, PC + 4);
+    // unknown opcodes and machine code routines are skipped
+    cpu.pc = PC;
+    cpu.execute(0xFFFF);
+    cpu.execute(0x0123);
+    assert_eq!(cpu.pc, PC + 4);
+}
+
 #[test]
 fn test_jp() {
     // 1nnn - JP addr Jump to location nnn.  
@@ -562,6 +679,7 @@ fn test_add_i_vx() {
 
 
 #[test]
+#[allow(clippy::identity_op)]
 fn test_ld_f_vx() {
     // Fx29 - LD F Vx - Set I = location of sprite for digit Vx.
     // The value of I is set to the location for the hexadecimal sprite corresponding to the value of Vx. See section 2.4, Display, for more information on the Chip-8 hexadecimal font.
@@ -633,3 +751,190 @@ fn test_ld_vx_i() {
     assert_eq!(cpu.v[5], 0x0);
 }
  
+
+
+#[test]
+fn test_tick_60_hz_instructions_per_frame() {
+    // each frame runs instructions_per_frame instructions, then ticks the timers once
+    let mut cpu = setup_cpu();
+    for addr in (PC as usize..PC as usize + 0x40).step_by(2) {
+        // 0x7001; add v[0], 1
+        cpu.ram[addr] = 0x70;
+        cpu.ram[addr + 1] = 0x01;
+    }
+    cpu.delay_timer = 5;
+    cpu.set_instructions_per_frame(3);
+    cpu.tick_60_hz(&[false; 16]);
+    assert_eq!(cpu.v[0], 3);
+    assert_eq!(cpu.pc, PC + 3 * OPCODE_SIZE);
+    assert_eq!(cpu.delay_timer, 4);
+    cpu.set_instructions_per_frame(7);
+    cpu.tick_60_hz(&[false; 16]);
+    assert_eq!(cpu.v[0], 10);
+    assert_eq!(cpu.delay_timer, 3);
+    // at least one instruction always runs per frame
+    cpu.set_instructions_per_frame(0);
+    assert_eq!(cpu.instructions_per_frame(), 1);
+}
+
+
+#[test]
+fn test_tick_60_hz_cosmac_vip_cycles() {
+    // each instruction costs its VIP machine cycles; the frame ends when
+    // the cycles between display interrupts are used up
+    let mut cpu = setup_cpu();
+    for addr in (PC as usize..PC as usize + 0x100).step_by(2) {
+        // 0x6001; ld v[0], 1
+        cpu.ram[addr] = 0x60;
+        cpu.ram[addr + 1] = 0x01;
+    }
+    cpu.delay_timer = 5;
+    cpu.set_timing_mode(TimingMode::CosmacVip);
+    let cycles_per_op = cpu.vip_cycles(0x6001);
+    assert_eq!(cycles_per_op, VIP_FETCH_CYCLES + 6);
+    let budget = VIP_CYCLES_PER_FRAME - VIP_DISPLAY_INTERRUPT_CYCLES;
+    let first_frame_ops = (budget + cycles_per_op - 1) / cycles_per_op;
+    cpu.tick_60_hz(&[false; 16]);
+    assert_eq!(cpu.pc, PC + first_frame_ops as u16 * OPCODE_SIZE);
+    assert_eq!(cpu.delay_timer, 4);
+    // the overrun is carried into the next frame
+    assert_eq!(cpu.vip_cycles_left, budget - first_frame_ops * cycles_per_op);
+}
+
+
+#[test]
+fn test_cosmac_vip_drw_waits_for_interrupt() {
+    let mut cpu = setup_cpu();
+    cpu.i = HEX_DIGIT_ADDR_START;
+    // 0x6001; ld v[0], 1
+    cpu.ram[PC as usize] = 0x60;
+    cpu.ram[PC as usize + 1] = 0x01;
+    // 0xD005; drw v[0], v[0], 5
+    cpu.ram[PC_NEXT as usize] = 0xD0;
+    cpu.ram[PC_NEXT as usize + 1] = 0x05;
+    // 0x1A04; jp 0xA04, spin after drawing
+    cpu.ram[PC_NEXT as usize + 2] = 0x1A;
+    cpu.ram[PC_NEXT as usize + 3] = 0x04;
+    cpu.set_timing_mode(TimingMode::CosmacVip);
+    let output = cpu.tick_60_hz(&[false; 16]);
+    assert!(!output.vram_changed);
+    assert_eq!(cpu.pc, PC_NEXT);
+    // drawn right after the next interrupt
+    let output = cpu.tick_60_hz(&[false; 16]);
+    assert!(output.vram_changed);
+    assert_eq!(cpu.vram[1][1], 1);
+    // unaligned sprites cost more than aligned ones
+    cpu.v[0] = 8;
+    let aligned = cpu.vip_cycles(0xD005);
+    cpu.v[0] = 9;
+    assert!(cpu.vip_cycles(0xD005) > aligned);
+}
+
+
+#[test]
+fn test_shift_quirk() {
+    // without the shift quirk 8xy6/8xyE shift Vy into Vx
+    let mut cpu = setup_cpu();
+    cpu.set_quirks(crate::quirks::ORIGINAL_CHIP8);
+    cpu.v[5] = 0x81;
+    cpu.execute(0x8456);
+    assert_eq!(cpu.v[4], 0x40);
+    assert_eq!(cpu.v[0xf], 1);
+    cpu.execute(0x845E);
+    assert_eq!(cpu.v[4], 0x02);
+    assert_eq!(cpu.v[0xf], 1);
+    assert_eq!(cpu.v[5], 0x81);
+}
+
+
+#[test]
+fn test_memory_quirks() {
+    // the VIP leaves I past the last register transferred
+    let mut cpu = setup_cpu();
+    cpu.set_quirks(crate::quirks::ORIGINAL_CHIP8);
+    cpu.i = 0x300;
+    cpu.execute(0xF455);
+    assert_eq!(cpu.i, 0x305);
+    cpu.execute(0xF265);
+    assert_eq!(cpu.i, 0x308);
+    // CHIP-48 is off by one
+    cpu.set_quirks(crate::quirks::CHIP48);
+    cpu.execute(0xF455);
+    assert_eq!(cpu.i, 0x30C);
+}
+
+
+#[test]
+fn test_jump_quirk() {
+    // Bxnn jumps to xnn + Vx
+    let mut cpu = setup_cpu();
+    cpu.set_quirks(crate::quirks::SUPERCHIP);
+    cpu.execute(0xB345);
+    assert_eq!(cpu.pc, 0x345 + 3);
+}
+
+
+#[test]
+fn test_logic_quirk() {
+    // the VIP's 8xy1/8xy2/8xy3 clobber VF
+    let mut cpu = setup_cpu();
+    cpu.set_quirks(crate::quirks::ORIGINAL_CHIP8);
+    for opcode in [0x8121, 0x8122, 0x8123].iter() {
+        cpu.v[0xf] = 1;
+        cpu.execute(*opcode);
+        assert_eq!(cpu.v[0xf], 0);
+    }
+}
+
+
+#[test]
+fn test_draw_clip_quirk() {
+    // without the wrap quirk sprites are clipped at the screen edges
+    let mut cpu = setup_cpu();
+    cpu.set_quirks(crate::quirks::ORIGINAL_CHIP8);
+    cpu.i = 0x300;
+    cpu.ram[0x300] = 0xFF;
+    cpu.ram[0x301] = 0xFF;
+    cpu.v[0] = DISPLAY_WIDTH as u8 - 4;
+    cpu.v[1] = DISPLAY_HEIGHT as u8 - 1;
+    cpu.execute(0xD012);
+    assert_eq!(cpu.vram[DISPLAY_HEIGHT - 1][DISPLAY_WIDTH - 1], 1);
+    assert_eq!(cpu.vram[DISPLAY_HEIGHT - 1][0], 0);
+    assert_eq!(cpu.vram[0][DISPLAY_WIDTH - 1], 0);
+    // the start position still wraps
+    cpu.v[0] = DISPLAY_WIDTH as u8;
+    cpu.v[1] = 0;
+    cpu.execute(0xD011);
+    assert_eq!(cpu.vram[0][0], 1);
+}
+
+
+#[test]
+fn test_vblank_quirk() {
+    // with the vblank quirk a frame ends after its first sprite
+    let mut cpu = setup_cpu();
+    cpu.set_quirks(crate::quirks::ORIGINAL_CHIP8);
+    cpu.i = HEX_DIGIT_ADDR_START;
+    for addr in (PC as usize..PC as usize + 0x20).step_by(2) {
+        // 0xD015; drw v[0], v[1], 5
+        cpu.ram[addr] = 0xD0;
+        cpu.ram[addr + 1] = 0x15;
+    }
+    cpu.tick_60_hz(&[false; 16]);
+    assert_eq!(cpu.pc, PC_NEXT);
+    cpu.tick_60_hz(&[false; 16]);
+    assert_eq!(cpu.pc, PC_NEXT + OPCODE_SIZE);
+}
+
+
+#[test]
+fn test_lockstep() {
+    use crate::lockstep;
+    use crate::reference::Reference;
+    // ld v0, 5; ld v2, 40; shr v0, v2; ld f, v0; drw v0, v1, 5; jp 20a
+    let rom = [0x60, 0x05, 0x62, 0x40, 0x80, 0x26, 0xF0, 0x29, 0xD0, 0x15, 0x12, 0x0A];
+    let mut cpu = Cpu::new();
+    cpu.load_rom(&rom).unwrap();
+    let mut reference = Reference::new(&rom, Quirks::default(), 0);
+    assert!(lockstep::run(&mut cpu, &mut reference, &[], 10, 10).is_none());
+}
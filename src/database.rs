@@ -0,0 +1,123 @@
+// per-rom settings from a local copy of the community chip-8-database
+// (https://github.com/chip-8/chip-8-database): programs.json,
+// sha1-hashes.json and platforms.json in one directory.
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::Path;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use chippy8::quirks::Quirks;
+
+#[derive(Deserialize)]
+struct Program {
+    title: String,
+    #[serde(default)]
+    roms: HashMap<String, RomEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RomEntry {
+    #[serde(default)]
+    platforms: Vec<String>,
+    // quirk overrides for this rom, by platform id
+    #[serde(default)]
+    quirky_platforms: HashMap<String, HashMap<String, bool>>,
+    tickrate: Option<u32>,
+    colors: Option<Colors>,
+    // "up", "down", "left", "right", "a", "b" ... -> chip-8 key
+    #[serde(default)]
+    keys: BTreeMap<String, u8>,
+}
+
+#[derive(Deserialize)]
+struct Colors {
+    #[serde(default)]
+    pixels: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Platform {
+    id: String,
+    default_tickrate: Option<u32>,
+    #[serde(default)]
+    quirks: HashMap<String, bool>,
+}
+
+// what the database knows about one rom
+pub struct RomSettings {
+    pub title: String,
+    // chip-8-database platform id, e.g. "originalChip8" or "superchip"
+    pub platform: Option<String>,
+    pub quirks: Option<Quirks>,
+    pub tickrate: Option<u32>,
+    // background, foreground, then the extra XO-CHIP plane colours
+    pub colors: Vec<String>,
+    pub keys: BTreeMap<String, u8>,
+}
+
+pub struct RomDatabase {
+    programs: Vec<Program>,
+    hashes: HashMap<String, usize>,
+    platforms: Vec<Platform>,
+}
+
+// None for a missing file
+fn read_json<T: DeserializeOwned>(dir: &Path, file: &str) -> Result<Option<T>, String> {
+    let path = dir.join(file);
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("{}: {}", path.display(), e)),
+    };
+    serde_json::from_str(&text).map(Some).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn read_required_json<T: DeserializeOwned>(dir: &Path, file: &str) -> Result<T, String> {
+    read_json(dir, file)?.ok_or_else(|| format!("{}: not found", dir.join(file).display()))
+}
+
+impl RomDatabase {
+    pub fn load(dir: &Path) -> Result<Self, String> {
+        // platforms.json only adds quirk tables, so it can be left out, but
+        // a broken one is still an error
+        let platforms = read_json(dir, "platforms.json")?.unwrap_or_default();
+        Ok(RomDatabase {
+            programs: read_required_json(dir, "programs.json")?,
+            hashes: read_required_json(dir, "sha1-hashes.json")?,
+            platforms,
+        })
+    }
+
+    pub fn lookup(&self, sha1: &str) -> Option<RomSettings> {
+        let program = self.programs.get(*self.hashes.get(sha1)?)?;
+        let rom = program.roms.get(sha1)?;
+        let platform_id = rom.platforms.first();
+        let platform = platform_id.and_then(|id| self.platforms.iter().find(|p| &p.id == id));
+        let quirks = platform_id.and_then(|id| {
+            let mut quirks = Quirks::preset(id).or_else(|| platform.map(|_| Quirks::default()))?;
+            let overrides = platform.map(|p| &p.quirks).into_iter()
+                .chain(rom.quirky_platforms.get(id));
+            for quirk_table in overrides {
+                for (name, &value) in quirk_table {
+                    quirks.set(name, value);
+                }
+            }
+            Some(quirks)
+        });
+        Some(RomSettings {
+            title: program.title.clone(),
+            platform: platform_id.cloned(),
+            quirks,
+            tickrate: rom.tickrate.or_else(|| platform.and_then(|p| p.default_tickrate)),
+            colors: rom.colors.as_ref().map(|c| c.pixels.clone()).unwrap_or_default(),
+            keys: rom.keys.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+#[path = "./database_tests.rs"]
+mod database_tests;
@@ -0,0 +1,105 @@
+use super::*;
+use std::env;
+use std::path::PathBuf;
+use std::process;
+use chippy8::quirks::{COWGOD, ORIGINAL_CHIP8, SUPERCHIP};
+
+const PROGRAMS: &str = r##"[
+    {"title": "Pong", "roms": {"aaaa": {
+        "platforms": ["originalChip8"], "tickrate": 15,
+        "colors": {"pixels": ["#000000", "#ffffff"]},
+        "keys": {"up": 1, "down": 4}
+    }}},
+    {"title": "Quirky", "roms": {"bbbb": {
+        "platforms": ["superchip"],
+        "quirkyPlatforms": {"superchip": {"shift": false}}
+    }}},
+    {"title": "Homebrew", "roms": {"cccc": {"platforms": ["myPlatform"]}}}
+]"##;
+
+const HASHES: &str = r#"{"aaaa": 0, "bbbb": 1, "cccc": 2}"#;
+
+const PLATFORMS: &str = r#"[
+    {"id": "superchip", "defaultTickrate": 30, "quirks": {"jump": false}},
+    {"id": "myPlatform", "defaultTickrate": 20, "quirks": {"wrap": false, "logic": true}}
+]"#;
+
+// a database directory of its own for each test, with platforms.json
+// only if it's given
+fn database_dir(name: &str, platforms: Option<&str>) -> PathBuf {
+    let dir = env::temp_dir().join(format!("chippy8-database-{}-{}", process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("programs.json"), PROGRAMS).unwrap();
+    fs::write(dir.join("sha1-hashes.json"), HASHES).unwrap();
+    if let Some(platforms) = platforms {
+        fs::write(dir.join("platforms.json"), platforms).unwrap();
+    }
+    dir
+}
+
+
+#[test]
+fn test_lookup() {
+    let dir = database_dir("lookup", Some(PLATFORMS));
+    let database = RomDatabase::load(&dir).unwrap();
+    let pong = database.lookup("aaaa").unwrap();
+    assert_eq!(pong.title, "Pong");
+    assert_eq!(pong.platform.as_deref(), Some("originalChip8"));
+    assert_eq!(pong.quirks, Some(ORIGINAL_CHIP8));
+    assert_eq!(pong.tickrate, Some(15));
+    assert_eq!(pong.colors, vec!["#000000", "#ffffff"]);
+    assert_eq!(pong.keys.get("down"), Some(&4));
+    assert!(database.lookup("dddd").is_none());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+
+#[test]
+fn test_quirk_overrides() {
+    let dir = database_dir("quirks", Some(PLATFORMS));
+    let database = RomDatabase::load(&dir).unwrap();
+    // platforms.json, then the rom's quirkyPlatforms, over the preset
+    let quirky = database.lookup("bbbb").unwrap();
+    let mut expected = SUPERCHIP;
+    expected.jump = false;
+    expected.shift = false;
+    assert_eq!(quirky.quirks, Some(expected));
+    assert_eq!(quirky.tickrate, Some(30));
+    // a platform with no preset starts from the default
+    let homebrew = database.lookup("cccc").unwrap();
+    let mut expected = COWGOD;
+    expected.wrap = false;
+    expected.logic = true;
+    assert_eq!(homebrew.quirks, Some(expected));
+    assert_eq!(homebrew.tickrate, Some(20));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+
+#[test]
+fn test_missing_platforms() {
+    let dir = database_dir("no-platforms", None);
+    let database = RomDatabase::load(&dir).unwrap();
+    let quirky = database.lookup("bbbb").unwrap();
+    let mut expected = SUPERCHIP;
+    expected.shift = false;
+    assert_eq!(quirky.quirks, Some(expected));
+    assert_eq!(quirky.tickrate, None);
+    // nothing to say what an unknown platform's quirks are
+    assert_eq!(database.lookup("cccc").unwrap().quirks, None);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+
+#[test]
+fn test_broken_files() {
+    let dir = database_dir("broken", Some("[{\"id\": "));
+    let error = RomDatabase::load(&dir).err().unwrap();
+    assert!(error.contains("platforms.json"), "{}", error);
+    fs::remove_file(dir.join("platforms.json")).unwrap();
+    fs::remove_file(dir.join("programs.json")).unwrap();
+    let error = RomDatabase::load(&dir).err().unwrap();
+    assert!(error.contains("programs.json"), "{}", error);
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,68 @@
+// mnemonics follow Cowgod's technical reference, like the comments in cpu.rs
+pub fn disassemble(opcode: u16) -> String {
+    let nnn = opcode & 0x0FFF;
+    let kk = opcode & 0x00FF;
+    let x = (opcode & 0x0F00) >> 8;
+    let y = (opcode & 0x00F0) >> 4;
+    let n = opcode & 0x000F;
+    match (opcode >> 12, n) {
+        _ if opcode == 0x00E0 => "CLS".to_string(),
+        _ if opcode == 0x00EE => "RET".to_string(),
+        (0x0, _) => format!("SYS {:03X}", nnn),
+        (0x1, _) => format!("JP {:03X}", nnn),
+        (0x2, _) => format!("CALL {:03X}", nnn),
+        (0x3, _) => format!("SE V{:X}, {:02X}", x, kk),
+        (0x4, _) => format!("SNE V{:X}, {:02X}", x, kk),
+        (0x5, 0x0) => format!("SE V{:X}, V{:X}", x, y),
+        (0x6, _) => format!("LD V{:X}, {:02X}", x, kk),
+        (0x7, _) => format!("ADD V{:X}, {:02X}", x, kk),
+        (0x8, 0x0) => format!("LD V{:X}, V{:X}", x, y),
+        (0x8, 0x1) => format!("OR V{:X}, V{:X}", x, y),
+        (0x8, 0x2) => format!("AND V{:X}, V{:X}", x, y),
+        (0x8, 0x3) => format!("XOR V{:X}, V{:X}", x, y),
+        (0x8, 0x4) => format!("ADD V{:X}, V{:X}", x, y),
+        (0x8, 0x5) => format!("SUB V{:X}, V{:X}", x, y),
+        (0x8, 0x6) => format!("SHR V{:X}, V{:X}", x, y),
+        (0x8, 0x7) => format!("SUBN V{:X}, V{:X}", x, y),
+        (0x8, 0xE) => format!("SHL V{:X}, V{:X}", x, y),
+        (0x9, 0x0) => format!("SNE V{:X}, V{:X}", x, y),
+        (0xA, _) => format!("LD I, {:03X}", nnn),
+        (0xB, _) => format!("JP V0, {:03X}", nnn),
+        (0xC, _) => format!("RND V{:X}, {:02X}", x, kk),
+        (0xD, _) => format!("DRW V{:X}, V{:X}, {:X}", x, y, n),
+        (0xE, _) if kk == 0x9E => format!("SKP V{:X}", x),
+        (0xE, _) if kk == 0xA1 => format!("SKNP V{:X}", x),
+        (0xF, _) => match kk {
+            0x07 => format!("LD V{:X}, DT", x),
+            0x0A => format!("LD V{:X}, K", x),
+            0x15 => format!("LD DT, V{:X}", x),
+            0x18 => format!("LD ST, V{:X}", x),
+            0x1E => format!("ADD I, V{:X}", x),
+            0x29 => format!("LD F, V{:X}", x),
+            0x33 => format!("LD B, V{:X}", x),
+            0x55 => format!("LD [I], V{:X}", x),
+            0x65 => format!("LD V{:X}, [I]", x),
+            _ => format!("DW {:04X}", opcode),
+        },
+        _ => format!("DW {:04X}", opcode),
+    }
+}
+
+// a linear listing of the rom as loaded at load_address. sprite data in
+// the middle of code is listed as instructions too.
+pub fn listing(rom: &[u8], load_address: u16) -> Vec<String> {
+    rom.chunks(2)
+        .enumerate()
+        .map(|(index, bytes)| {
+            let addr = load_address as usize + index * 2;
+            match *bytes {
+                [high, low] => {
+                    let opcode = (high as u16) << 8 | low as u16;
+                    format!("{:03X}: {:04X}  {}", addr, opcode, disassemble(opcode))
+                }
+                [byte] => format!("{:03X}: {:02X}    DB {:02X}", addr, byte, byte),
+                _ => unreachable!(),
+            }
+        })
+        .collect()
+}
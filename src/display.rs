@@ -1,58 +1,358 @@
-use sdl2;
 use sdl2::pixels;
-use sdl2::render::Canvas;
-use sdl2::video::Window;
+use sdl2::pixels::PixelFormatEnum;
 use sdl2::rect::Rect;
+use sdl2::render::{Canvas, Texture};
+use sdl2::video::{FullscreenType, Window};
 
-use crate::cpu::DISPLAY_WIDTH;
-use crate::cpu::DISPLAY_HEIGHT;
+use chippy8::cpu::DISPLAY_WIDTH;
+use chippy8::cpu::DISPLAY_HEIGHT;
+use chippy8::fonts::{small_glyph, SMALL_GLYPH_HEIGHT, SMALL_GLYPH_WIDTH};
+use chippy8::fonts::{TEXT_FONT, TEXT_FONT_FIRST, TEXT_GLYPH_HEIGHT, TEXT_GLYPH_WIDTH};
+use crate::palette::Palette;
+use crate::persistence::{Persistence, PersistenceFilter};
+
+const BYTES_PER_PIXEL: usize = 3;
+
+// text cells are a glyph with a pixel of space to its right and a few below
+const TEXT_CELL_WIDTH: u32 = TEXT_GLYPH_WIDTH as u32 + 1;
+const TEXT_CELL_HEIGHT: u32 = TEXT_GLYPH_HEIGHT as u32 + 3;
+// text is scaled up in whole steps, keeping about this many rows on screen
+const TEXT_ROWS: u32 = 24;
+// the on-screen display is smaller, to cover less of the game, and in
+// the 4x5 font the hex digits are drawn with
+const OSD_ROWS: u32 = 48;
+const OSD_CELL_WIDTH: u32 = SMALL_GLYPH_WIDTH as u32 + 1;
+const OSD_CELL_HEIGHT: u32 = SMALL_GLYPH_HEIGHT as u32 + 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextStyle {
+    Normal,
+    // drawn inverted, for the selected line of a menu
+    Highlighted,
+    // headings and help, in the palette's third colour
+    Dim,
+}
+
+pub struct TextLine {
+    pub text: String,
+    pub style: TextStyle,
+}
+
+impl TextLine {
+    pub fn new(text: impl Into<String>, style: TextStyle) -> Self {
+        TextLine { text: text.into(), style }
+    }
+}
+
+// the lit pixels of a line of text with its top left corner at x, y
+fn text_rects(text: &str, x: i32, y: i32, pixel: u32) -> Vec<Rect> {
+    let mut rects = Vec::new();
+    for (n, c) in text.chars().enumerate() {
+        let index = (c as usize).wrapping_sub(TEXT_FONT_FIRST as usize);
+        let glyph = TEXT_FONT.get(index).unwrap_or(&TEXT_FONT['?' as usize - TEXT_FONT_FIRST as usize]);
+        let left = x + (n as u32 * TEXT_CELL_WIDTH * pixel) as i32;
+        for (column, bits) in glyph.iter().enumerate() {
+            for row in 0..TEXT_GLYPH_HEIGHT {
+                if bits >> row & 1 == 1 {
+                    let px = left + (column as u32 * pixel) as i32;
+                    let py = y + (row as u32 * pixel) as i32;
+                    rects.push(Rect::new(px, py, pixel, pixel));
+                }
+            }
+        }
+    }
+    rects
+}
+
+// the same in the 4x5 font
+fn small_text_rects(text: &str, x: i32, y: i32, pixel: u32) -> Vec<Rect> {
+    let mut rects = Vec::new();
+    for (n, c) in text.chars().enumerate() {
+        let left = x + (n as u32 * OSD_CELL_WIDTH * pixel) as i32;
+        for (row, bits) in small_glyph(c).iter().enumerate() {
+            for column in 0..SMALL_GLYPH_WIDTH {
+                if bits << column & 0x80 != 0 {
+                    let px = left + (column as u32 * pixel) as i32;
+                    let py = y + (row as u32 * pixel) as i32;
+                    rects.push(Rect::new(px, py, pixel, pixel));
+                }
+            }
+        }
+    }
+    rects
+}
+
+// how the framebuffer fills a window of a different shape
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scaling {
+    // as large as fits with the aspect ratio kept, letterboxed
+    Fit,
+    // the largest whole multiple that fits, letterboxed
+    Integer,
+    // the whole window, aspect ratio be damned
+    Stretch,
+}
+
+impl Scaling {
+    pub fn from_name(name: &str) -> Option<Scaling> {
+        match name {
+            "fit" => Some(Scaling::Fit),
+            "integer" => Some(Scaling::Integer),
+            "stretch" => Some(Scaling::Stretch),
+            _ => None,
+        }
+    }
+
+    pub fn next(self) -> Scaling {
+        match self {
+            Scaling::Fit => Scaling::Integer,
+            Scaling::Integer => Scaling::Stretch,
+            Scaling::Stretch => Scaling::Fit,
+        }
+    }
+
+    // where a frame_width x frame_height framebuffer goes in the window
+    pub fn target(self, window: (u32, u32), frame_width: u32, frame_height: u32) -> Rect {
+        let (window_width, window_height) = window;
+        let (width, height) = match self {
+            Scaling::Stretch => (window_width, window_height),
+            Scaling::Integer => {
+                let scale = (window_width / frame_width).min(window_height / frame_height).max(1);
+                (frame_width * scale, frame_height * scale)
+            }
+            Scaling::Fit => {
+                if window_width * frame_height > window_height * frame_width {
+                    (window_height * frame_width / frame_height, window_height)
+                } else {
+                    (window_width, window_width * frame_height / frame_width)
+                }
+            }
+        };
+        let x = (window_width as i32 - width as i32) / 2;
+        let y = (window_height as i32 - height as i32) / 2;
+        Rect::new(x, y, width.max(1), height.max(1))
+    }
+}
 
 pub struct Display {
     canvas: Canvas<Window>,
-    scale: u32,
-    pixel_lit: pixels::Color,
-    pixel_unlit: pixels::Color,
+    // the framebuffer at native resolution; the renderer scales it up to
+    // the window with nearest-neighbour filtering, so hi-res modes only
+    // need a bigger texture
+    texture: Texture,
+    // RGB24 staging buffer for the texture
+    pixels: Vec<u8>,
+    palette: Palette,
+    filter: PersistenceFilter,
+    scaling: Scaling,
+    // lines drawn over the game, top left
+    osd: Vec<String>,
 }
 
 impl Display {
-    pub fn new(sdl: &sdl2::Sdl, scale: u32) -> Self {
+    // position None centres the window
+    pub fn new(sdl: &sdl2::Sdl, size: (u32, u32), position: Option<(i32, i32)>, fullscreen: bool) -> Self {
         println!("getting video subsystem");
         let video = sdl.video().unwrap();
         println!("opening window");
-        let window = video.window(
-            "CHIPPY8",
-            DISPLAY_WIDTH as u32 * scale,
-            DISPLAY_HEIGHT as u32 * scale)
+        let mut window_builder = video.window("CHIPPY8", size.0, size.1);
+        window_builder.resizable();
+        if let Some((x, y)) = position {
+            window_builder.position(x, y);
+        }
+        if fullscreen {
+            window_builder.fullscreen_desktop();
+        }
+        let window = window_builder
             .build()
             .unwrap();
+        // keep pixels square and sharp when scaling the texture up
+        sdl2::hint::set("SDL_RENDER_SCALE_QUALITY", "0");
         println!("getting canvas");
         let mut canvas = window.into_canvas()
             .build()
             .unwrap();
         println!("clearing canvas");
+        canvas.set_draw_color(pixels::Color::RGB(0, 0, 0));
         canvas.clear();
         println!("presenting canvas");
         canvas.present();
-        canvas.set_draw_color(pixels::Color::RGB(0, 0, 0));
+        let texture = canvas
+            .create_texture_streaming(PixelFormatEnum::RGB24, DISPLAY_WIDTH as u32, DISPLAY_HEIGHT as u32)
+            .unwrap();
         Display {
-            canvas: canvas,
-            scale: scale,
-            pixel_lit: pixels::Color::RGB(0xff, 0xbf, 0),
-            pixel_unlit: pixels::Color::RGB(0, 0, 0),
+            canvas,
+            texture,
+            pixels: vec![0; DISPLAY_WIDTH * DISPLAY_HEIGHT * BYTES_PER_PIXEL],
+            palette: Palette::default(),
+            filter: PersistenceFilter::new(Persistence::Off),
+            scaling: Scaling::Fit,
+            osd: Vec::new(),
         }
     }
 
+    pub fn scaling(&self) -> Scaling {
+        self.scaling
+    }
+
+    pub fn set_scaling(&mut self, scaling: Scaling) {
+        self.scaling = scaling;
+        self.render();
+    }
+
+    pub fn is_fullscreen(&self) -> bool {
+        self.canvas.window().fullscreen_state() != FullscreenType::Off
+    }
+
+    pub fn toggle_fullscreen(&mut self) {
+        let fullscreen = if self.is_fullscreen() { FullscreenType::Off } else { FullscreenType::Desktop };
+        if let Err(e) = self.canvas.window_mut().set_fullscreen(fullscreen) {
+            println!("unable to change fullscreen mode: {}", e);
+        }
+        self.render();
+    }
+
+    pub fn set_title(&mut self, title: &str) {
+        if let Err(e) = self.canvas.window_mut().set_title(title) {
+            println!("unable to set the window title: {}", e);
+        }
+    }
+
+    fn text_pixel_size(&self, rows: u32, cell_height: u32) -> u32 {
+        let (_, height) = self.canvas.output_size().unwrap_or((0, 0));
+        (height / (rows * cell_height)).max(1)
+    }
+
+    // how many columns and rows of text fit in the window
+    pub fn text_grid(&self) -> (usize, usize) {
+        let (width, height) = self.canvas.output_size().unwrap_or((0, 0));
+        let pixel = self.text_pixel_size(TEXT_ROWS, TEXT_CELL_HEIGHT);
+        ((width / (TEXT_CELL_WIDTH * pixel)) as usize, (height / (TEXT_CELL_HEIGHT * pixel)) as usize)
+    }
+
+    // a screen of text in place of the framebuffer, one line per row, in
+    // the palette's colours
+    pub fn draw_text_screen(&mut self, lines: &[TextLine]) {
+        let pixel = self.text_pixel_size(TEXT_ROWS, TEXT_CELL_HEIGHT);
+        let (width, _) = self.canvas.output_size().unwrap_or((0, 0));
+        let colors = self.palette.colors;
+        self.canvas.set_draw_color(colors[0]);
+        self.canvas.clear();
+        for (row, line) in lines.iter().enumerate() {
+            let y = (row as u32 * TEXT_CELL_HEIGHT * pixel) as i32;
+            let color = match line.style {
+                TextStyle::Normal => colors[1],
+                TextStyle::Highlighted => {
+                    self.canvas.set_draw_color(colors[1]);
+                    let _ = self.canvas.fill_rect(Rect::new(0, y, width, TEXT_CELL_HEIGHT * pixel));
+                    colors[0]
+                }
+                TextStyle::Dim => colors[2],
+            };
+            self.canvas.set_draw_color(color);
+            // a pixel of margin on the left, and the glyphs centred in the row
+            let rects = text_rects(&line.text, pixel as i32, y + pixel as i32, pixel);
+            let _ = self.canvas.fill_rects(&rects);
+        }
+        self.canvas.present();
+    }
+
+    // redraws only when the lines change, so this can be called every frame
+    pub fn set_osd(&mut self, lines: Vec<String>) {
+        if lines != self.osd {
+            self.osd = lines;
+            self.render();
+        }
+    }
+
+    // each line on a strip of the background colour, so it reads over
+    // whatever the game has drawn
+    fn draw_osd(&mut self) {
+        let pixel = self.text_pixel_size(OSD_ROWS, OSD_CELL_HEIGHT);
+        let colors = self.palette.colors;
+        for (row, text) in self.osd.iter().enumerate() {
+            let y = (row as u32 * OSD_CELL_HEIGHT * pixel) as i32;
+            let width = (text.chars().count() as u32 * OSD_CELL_WIDTH + 1) * pixel;
+            self.canvas.set_draw_color(colors[0]);
+            let _ = self.canvas.fill_rect(Rect::new(0, y, width, OSD_CELL_HEIGHT * pixel));
+            self.canvas.set_draw_color(colors[1]);
+            let _ = self.canvas.fill_rects(&small_text_rects(text, pixel as i32, y + pixel as i32, pixel));
+        }
+    }
+
+    // the windowed size and position, to restore next time
+    pub fn window_geometry(&self) -> ((u32, u32), (i32, i32)) {
+        let window = self.canvas.window();
+        (window.size(), window.position())
+    }
+
+    // after the window was resized or exposed
+    pub fn redraw(&mut self) {
+        self.render();
+    }
+
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+        self.render();
+    }
+
+    pub fn persistence(&self) -> Persistence {
+        self.filter.mode()
+    }
+
+    pub fn set_persistence(&mut self, persistence: Persistence) {
+        self.filter.set_mode(persistence);
+    }
+
+    // false while pixels are still fading, so draw needs calling every
+    // frame even if vram hasn't changed
+    pub fn is_settled(&self) -> bool {
+        self.filter.is_settled()
+    }
+
+    // call once per emulated frame while vram is changing or the display
+    // isn't settled
     pub fn draw(&mut self, vram: &[[u8; DISPLAY_WIDTH]; DISPLAY_HEIGHT]) {
-        for vram_row in 0..DISPLAY_HEIGHT {
-            for vram_column in 0..DISPLAY_WIDTH {
-                let is_colored = vram[vram_row][vram_column] != 0;
-                let color = if is_colored { self.pixel_lit } else { self.pixel_unlit };
-                let y: i32 = vram_row as i32 * self.scale as i32;
-                let x: i32 = vram_column as i32 * self.scale as i32;
-                self.canvas.set_draw_color(color);
-                let _ = self.canvas.fill_rect(Rect::new(x, y, self.scale, self.scale));
+        self.filter.update(vram);
+        self.render();
+    }
+
+    fn render(&mut self) {
+        let background = self.palette.colors[0];
+        let row_bytes = DISPLAY_WIDTH * BYTES_PER_PIXEL;
+        for (vram_row, row_pixels) in self.pixels.chunks_mut(row_bytes).enumerate() {
+            for (vram_column, rgb) in row_pixels.chunks_mut(BYTES_PER_PIXEL).enumerate() {
+                let (value, intensity) = self.filter.pixel(vram_row, vram_column);
+                let color = blend(background, self.palette.colors[value as usize & 0x3], intensity);
+                rgb.copy_from_slice(&[color.r, color.g, color.b]);
             }
         }
+        if let Err(e) = self.texture.update(None, &self.pixels, row_bytes) {
+            println!("unable to update framebuffer texture: {}", e);
+        }
+        let target = match self.canvas.output_size() {
+            Ok(output_size) => self.scaling.target(output_size, DISPLAY_WIDTH as u32, DISPLAY_HEIGHT as u32),
+            Err(_) => return,
+        };
+        // letterbox bars
+        self.canvas.set_draw_color(pixels::Color::RGB(0, 0, 0));
+        self.canvas.clear();
+        let _ = self.canvas.copy(&self.texture, None, target);
+        self.draw_osd();
         self.canvas.present();
     }
 }
+
+// software alpha blend of a lit colour over the background
+fn blend(background: pixels::Color, color: pixels::Color, alpha: f32) -> pixels::Color {
+    let mix = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * alpha).round() as u8;
+    pixels::Color::RGB(mix(background.r, color.r), mix(background.g, color.g), mix(background.b, color.b))
+}
+
+#[cfg(test)]
+#[path = "./display_tests.rs"]
+mod display_tests;
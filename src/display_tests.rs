@@ -0,0 +1,45 @@
+use super::*;
+
+
+#[test]
+fn test_fit() {
+    // wider than 2:1, so bars left and right
+    assert_eq!(Scaling::Fit.target((1000, 300), 64, 32), Rect::new(200, 0, 600, 300));
+    // taller, bars above and below, the odd pixel going to the bottom one
+    assert_eq!(Scaling::Fit.target((641, 501), 64, 32), Rect::new(0, 90, 641, 320));
+    // exactly 2:1
+    assert_eq!(Scaling::Fit.target((1024, 512), 64, 32), Rect::new(0, 0, 1024, 512));
+    // hi-res frames keep the same shape
+    assert_eq!(Scaling::Fit.target((1000, 300), 128, 64), Rect::new(200, 0, 600, 300));
+}
+
+
+#[test]
+fn test_integer() {
+    assert_eq!(Scaling::Integer.target((1000, 300), 64, 32), Rect::new(212, 6, 576, 288));
+    assert_eq!(Scaling::Integer.target((641, 500), 64, 32), Rect::new(0, 90, 640, 320));
+    assert_eq!(Scaling::Integer.target((1000, 300), 128, 64), Rect::new(244, 22, 512, 256));
+    // smaller than the frame still draws it at one to one, cropped
+    assert_eq!(Scaling::Integer.target((50, 20), 64, 32), Rect::new(-7, -6, 64, 32));
+}
+
+
+#[test]
+fn test_stretch() {
+    assert_eq!(Scaling::Stretch.target((1000, 300), 64, 32), Rect::new(0, 0, 1000, 300));
+    assert_eq!(Scaling::Stretch.target((641, 500), 128, 64), Rect::new(0, 0, 641, 500));
+    // a minimised window has no size
+    assert_eq!(Scaling::Stretch.target((0, 0), 64, 32), Rect::new(0, 0, 1, 1));
+}
+
+
+#[test]
+fn test_next() {
+    let mut scaling = Scaling::Fit;
+    for expected in [Scaling::Integer, Scaling::Stretch, Scaling::Fit].iter() {
+        scaling = scaling.next();
+        assert_eq!(scaling, *expected);
+        assert_eq!(Scaling::from_name(&format!("{:?}", scaling).to_lowercase()), Some(scaling));
+    }
+    assert_eq!(Scaling::from_name("zoom"), None);
+}
@@ -0,0 +1,26 @@
+use super::*;
+
+
+#[test]
+fn test_small_glyph() {
+    assert_eq!(small_glyph('0'), [0xF0, 0x90, 0x90, 0x90, 0xF0]);
+    assert_eq!(small_glyph('f'), [0xF0, 0x80, 0xF0, 0x80, 0x80]);
+    assert_eq!(small_glyph('a'), small_glyph('A'));
+    assert_eq!(small_glyph('s'), [0x70, 0x80, 0x60, 0x10, 0xE0]);
+    // not in the font
+    assert_eq!(small_glyph('~'), small_glyph('?'));
+    assert_eq!(small_glyph('é'), small_glyph('?'));
+}
+
+
+#[test]
+fn test_small_font() {
+    // every letter, drawn in four columns, once each
+    for c in 'A'..='Z' {
+        assert!(c.is_ascii_hexdigit() || SMALL_FONT.iter().any(|&(glyph, _)| glyph == c), "{}", c);
+    }
+    for (n, (c, glyph)) in SMALL_FONT.iter().enumerate() {
+        assert!(glyph.iter().all(|row| row & 0x0F == 0), "{}", c);
+        assert!(!SMALL_FONT[n + 1..].iter().any(|(other, _)| other == c), "{}", c);
+    }
+}
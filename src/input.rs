@@ -1,70 +1,233 @@
-use sdl2::keyboard::Keycode;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::GameControllerSubsystem;
+use sdl2::keyboard::Scancode;
 use sdl2::event::Event;
+use crate::config::KeyBindings;
+
+// how far a stick or trigger must move before it counts as pressed
+const AXIS_THRESHOLD: i16 = 16384;
+
+// keys the game loop takes for itself before they reach the bindings:
+// speed, pacing, fast-forward, turbo, volume and the function keys.
+// they go by keycode there, which on these keys is the scancode.
+const HOTKEYS: [Scancode; 19] = [
+    Scancode::Escape, Scancode::PageUp, Scancode::PageDown, Scancode::Home,
+    Scancode::Tab, Scancode::Grave, Scancode::Minus, Scancode::Equals,
+    Scancode::F1, Scancode::F2, Scancode::F3, Scancode::F4, Scancode::F5, Scancode::F6,
+    Scancode::F7, Scancode::F8, Scancode::F9, Scancode::F11, Scancode::F12,
+];
+
+// a controller button, or one direction of an axis
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum PadInput {
+    Button(Button),
+    Axis(Axis, bool),
+}
+
+impl PadInput {
+    // SDL mapping names: "a", "dpup", ... for buttons, and "leftx-",
+    // "lefty+", "righttrigger" ... for axes
+    pub fn from_name(name: &str) -> Option<PadInput> {
+        if let Some(axis) = name.strip_suffix('-') {
+            return Axis::from_string(axis).map(|axis| PadInput::Axis(axis, false));
+        }
+        if let Some(axis) = name.strip_suffix('+') {
+            return Axis::from_string(axis).map(|axis| PadInput::Axis(axis, true));
+        }
+        Button::from_string(name).map(PadInput::Button)
+            .or_else(|| Axis::from_string(name).map(|axis| PadInput::Axis(axis, true)))
+    }
+}
+
+// both directions of an axis, and whether the axis is pushed far enough
+// that way to count as pressed
+fn axis_directions(axis: Axis, value: i16) -> [(PadInput, bool); 2] {
+    [
+        (PadInput::Axis(axis, true), value > AXIS_THRESHOLD),
+        (PadInput::Axis(axis, false), value < -AXIS_THRESHOLD),
+    ]
+}
+
+// host input -> chip-8 key. a chip-8 key can have several host inputs.
+pub struct Bindings<T> {
+    bindings: HashMap<T, usize>,
+}
+
+pub type KeyMap = Bindings<Scancode>;
+pub type PadMap = Bindings<PadInput>;
+
+impl<T: Copy + Eq + Hash> Bindings<T> {
+    // each layer replaces the bindings of the chip-8 keys it lists.
+    // reserved inputs would never reach the bindings, so they're refused.
+    pub fn new(layers: &[&KeyBindings], parse: fn(&str) -> Option<T>, reserved: &[T]) -> Self {
+        let mut bindings: HashMap<T, usize> = HashMap::new();
+        for layer in layers {
+            for (key_name, input_names) in layer.iter() {
+                let key = match usize::from_str_radix(key_name, 16) {
+                    Ok(key) if key < 16 => key,
+                    _ => {
+                        println!("ignoring binding for unknown chip-8 key '{}'", key_name);
+                        continue;
+                    }
+                };
+                bindings.retain(|_, bound_key| *bound_key != key);
+                for input_name in input_names {
+                    match parse(input_name) {
+                        Some(input) if reserved.contains(&input) => {
+                            println!("ignoring '{}' for chip-8 key {:x}, it's taken by a hotkey", input_name, key);
+                        }
+                        Some(input) => { bindings.insert(input, key); }
+                        None => println!("ignoring unknown input name '{}'", input_name),
+                    }
+                }
+            }
+        }
+        Bindings { bindings }
+    }
+
+    pub fn key(&self, input: T) -> Option<usize> {
+        self.bindings.get(&input).copied()
+    }
+}
+
+impl KeyMap {
+    pub fn for_keyboard(layers: &[&KeyBindings]) -> Self {
+        Bindings::new(layers, Scancode::from_name, &HOTKEYS)
+    }
+}
+
+impl PadMap {
+    pub fn for_controller(layers: &[&KeyBindings]) -> Self {
+        Bindings::new(layers, PadInput::from_name, &[])
+    }
+}
 
 pub struct Input {
+    keymap: KeyMap,
+    padmap: PadMap,
+    // open controllers by joystick instance id
+    controllers: HashMap<i32, GameController>,
+    held_keys: HashSet<Scancode>,
+    held_pad_inputs: HashSet<(i32, PadInput)>,
     keys_pressed: [bool; 16],
 }
 
 
 impl Input {
-    pub fn new() -> Self {
+    pub fn new(keymap: KeyMap, padmap: PadMap) -> Self {
         Input {
+            keymap,
+            padmap,
+            controllers: HashMap::new(),
+            held_keys: HashSet::new(),
+            held_pad_inputs: HashSet::new(),
             keys_pressed: [false; 16],
         }
     }
 
+    // a new game's bindings, keeping the controllers that are open
+    pub fn set_bindings(&mut self, keymap: KeyMap, padmap: PadMap) {
+        self.keymap = keymap;
+        self.padmap = padmap;
+        for key in 0..self.keys_pressed.len() {
+            self.update(key);
+        }
+    }
+
     pub fn keys_pressed(&self) -> &[bool; 16] {
         &self.keys_pressed
     }
 
     pub fn keydown(&mut self, event: sdl2::event::Event) {
-        /* keyboard layout:
-           1 2 3 c
-           4 5 6 d
-           7 8 9 e
-           a 0 b f
-        */
-        match event {
-            Event::KeyDown { keycode: Some(Keycode::Num1), .. } => { self.keys_pressed[1] = true },
-            Event::KeyDown { keycode: Some(Keycode::Num2), .. } => { self.keys_pressed[2] = true },
-            Event::KeyDown { keycode: Some(Keycode::Num3), .. } => { self.keys_pressed[3] = true },
-            Event::KeyDown { keycode: Some(Keycode::Num4), .. } => { self.keys_pressed[0xc] = true }
-            Event::KeyDown { keycode: Some(Keycode::Q), .. } => { self.keys_pressed[4] = true }
-            Event::KeyDown { keycode: Some(Keycode::W), .. } => { self.keys_pressed[5] = true }
-            Event::KeyDown { keycode: Some(Keycode::E), .. } => { self.keys_pressed[6] = true }
-            Event::KeyDown { keycode: Some(Keycode::R), .. } => { self.keys_pressed[0xd] = true }
-            Event::KeyDown { keycode: Some(Keycode::A), .. } => { self.keys_pressed[7] = true }
-            Event::KeyDown { keycode: Some(Keycode::S), .. } => { self.keys_pressed[8] = true }
-            Event::KeyDown { keycode: Some(Keycode::D), .. } => { self.keys_pressed[9] = true }
-            Event::KeyDown { keycode: Some(Keycode::F), .. } => { self.keys_pressed[0xe] = true }
-            Event::KeyDown { keycode: Some(Keycode::Z), .. } => { self.keys_pressed[0xa] = true }
-            Event::KeyDown { keycode: Some(Keycode::X), .. } => { self.keys_pressed[0] = true }
-            Event::KeyDown { keycode: Some(Keycode::C), .. } => { self.keys_pressed[0xb] = true }
-            Event::KeyDown { keycode: Some(Keycode::V), .. } => { self.keys_pressed[0xf] = true }
-            _ => {},
+        if let Event::KeyDown { scancode: Some(scancode), .. } = event {
+            self.held_keys.insert(scancode);
+            if let Some(key) = self.keymap.key(scancode) {
+                self.update(key);
+            }
         }
     }
 
     pub fn keyup(&mut self, event: sdl2::event::Event) {
+        if let Event::KeyUp { scancode: Some(scancode), .. } = event {
+            self.held_keys.remove(&scancode);
+            if let Some(key) = self.keymap.key(scancode) {
+                self.update(key);
+            }
+        }
+    }
+
+    // ControllerDeviceAdded carries a joystick index, everything else the
+    // instance id of an open controller
+    pub fn add_controller(&mut self, subsystem: &GameControllerSubsystem, joystick_index: u32) {
+        match subsystem.open(joystick_index) {
+            Ok(controller) => {
+                println!("controller connected: {}", controller.name());
+                self.controllers.insert(controller.instance_id(), controller);
+            }
+            Err(e) => println!("unable to open controller {}: {}", joystick_index, e),
+        }
+    }
+
+    pub fn remove_controller(&mut self, instance_id: i32) {
+        if let Some(controller) = self.controllers.remove(&instance_id) {
+            println!("controller disconnected: {}", controller.name());
+            let released: Vec<PadInput> = self.held_pad_inputs.iter()
+                .filter(|(which, _)| *which == instance_id)
+                .map(|&(_, input)| input)
+                .collect();
+            for input in released {
+                self.release(instance_id, input);
+            }
+        }
+    }
+
+    pub fn controller_event(&mut self, event: sdl2::event::Event) {
         match event {
-            Event::KeyUp { keycode: Some(Keycode::Num1), .. } => { self.keys_pressed[1] = false },
-            Event::KeyUp { keycode: Some(Keycode::Num2), .. } => { self.keys_pressed[2] = false },
-            Event::KeyUp { keycode: Some(Keycode::Num3), .. } => { self.keys_pressed[3] = false },
-            Event::KeyUp { keycode: Some(Keycode::Num4), .. } => { self.keys_pressed[0xc] = false }
-            Event::KeyUp { keycode: Some(Keycode::Q), .. } => { self.keys_pressed[4] = false }
-            Event::KeyUp { keycode: Some(Keycode::W), .. } => { self.keys_pressed[5] = false }
-            Event::KeyUp { keycode: Some(Keycode::E), .. } => { self.keys_pressed[6] = false }
-            Event::KeyUp { keycode: Some(Keycode::R), .. } => { self.keys_pressed[0xd] = false }
-            Event::KeyUp { keycode: Some(Keycode::A), .. } => { self.keys_pressed[7] = false }
-            Event::KeyUp { keycode: Some(Keycode::S), .. } => { self.keys_pressed[8] = false }
-            Event::KeyUp { keycode: Some(Keycode::D), .. } => { self.keys_pressed[9] = false }
-            Event::KeyUp { keycode: Some(Keycode::F), .. } => { self.keys_pressed[0xe] = false }
-            Event::KeyUp { keycode: Some(Keycode::Z), .. } => { self.keys_pressed[0xa] = false }
-            Event::KeyUp { keycode: Some(Keycode::X), .. } => { self.keys_pressed[0] = false }
-            Event::KeyUp { keycode: Some(Keycode::C), .. } => { self.keys_pressed[0xb] = false }
-            Event::KeyUp { keycode: Some(Keycode::V), .. } => { self.keys_pressed[0xf] = false }
-            _ => {},
+            Event::ControllerButtonDown { which, button, .. } => {
+                self.press(which, PadInput::Button(button));
+            }
+            Event::ControllerButtonUp { which, button, .. } => {
+                self.release(which, PadInput::Button(button));
+            }
+            Event::ControllerAxisMotion { which, axis, value, .. } => {
+                for &(input, pushed) in axis_directions(axis, value).iter() {
+                    if pushed {
+                        self.press(which, input);
+                    } else {
+                        self.release(which, input);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn press(&mut self, which: i32, input: PadInput) {
+        self.held_pad_inputs.insert((which, input));
+        if let Some(key) = self.padmap.key(input) {
+            self.update(key);
         }
-     }
+    }
+
+    fn release(&mut self, which: i32, input: PadInput) {
+        self.held_pad_inputs.remove(&(which, input));
+        if let Some(key) = self.padmap.key(input) {
+            self.update(key);
+        }
+    }
+
+    // a chip-8 key stays down while any of its host inputs is held
+    fn update(&mut self, key: usize) {
+        let keymap = &self.keymap;
+        let padmap = &self.padmap;
+        self.keys_pressed[key] =
+            self.held_keys.iter().any(|&held| keymap.key(held) == Some(key)) ||
+            self.held_pad_inputs.iter().any(|&(_, held)| padmap.key(held) == Some(key));
+    }
 }
 
+#[cfg(test)]
+#[path = "./input_tests.rs"]
+mod input_tests;
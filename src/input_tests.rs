@@ -0,0 +1,134 @@
+use super::*;
+use crate::config::{controller_bindings_from_hints, default_controller_bindings, default_key_bindings, Config};
+
+// Scancode::from_name asks SDL, so the tests name keys themselves
+fn scancode(name: &str) -> Option<Scancode> {
+    Some(match name {
+        "1" => Scancode::Num1,
+        "X" => Scancode::X,
+        "Q" => Scancode::Q,
+        "W" => Scancode::W,
+        "Up" => Scancode::Up,
+        "Down" => Scancode::Down,
+        "Tab" => Scancode::Tab,
+        "F5" => Scancode::F5,
+        _ => return None,
+    })
+}
+
+fn layer(bindings: &[(&str, &[&str])]) -> KeyBindings {
+    bindings.iter()
+        .map(|&(key, inputs)| (key.to_string(), inputs.iter().map(|input| input.to_string()).collect()))
+        .collect()
+}
+
+
+#[test]
+fn test_layers() {
+    let base = layer(&[("1", &["1"]), ("5", &["W"]), ("0", &["X"])]);
+    let top = layer(&[("5", &["Up", "Down"]), ("0", &[])]);
+    let keymap = Bindings::new(&[&base, &top], scancode, &[]);
+    // the top layer's keys replace the base's, the rest are kept
+    assert_eq!(keymap.key(Scancode::Num1), Some(0x1));
+    assert_eq!(keymap.key(Scancode::W), None);
+    assert_eq!(keymap.key(Scancode::Up), Some(0x5));
+    assert_eq!(keymap.key(Scancode::Down), Some(0x5));
+    // an empty list unbinds the key
+    assert_eq!(keymap.key(Scancode::X), None);
+    // a host key moved to another chip-8 key is only on the new one
+    let moved = layer(&[("6", &["1"])]);
+    let keymap = Bindings::new(&[&base, &moved], scancode, &[]);
+    assert_eq!(keymap.key(Scancode::Num1), Some(0x6));
+}
+
+
+#[test]
+fn test_unknown_names() {
+    let bindings = layer(&[("g", &["Q"]), ("10", &["Q"]), ("a", &["Nowhere", "W"])]);
+    let keymap = Bindings::new(&[&bindings], scancode, &[]);
+    assert_eq!(keymap.key(Scancode::Q), None);
+    assert_eq!(keymap.key(Scancode::W), Some(0xa));
+}
+
+
+#[test]
+fn test_hotkeys() {
+    let bindings = layer(&[("5", &["Tab", "W"]), ("6", &["F5"])]);
+    let keymap = Bindings::new(&[&bindings], scancode, &HOTKEYS);
+    assert_eq!(keymap.key(Scancode::Tab), None);
+    assert_eq!(keymap.key(Scancode::F5), None);
+    assert_eq!(keymap.key(Scancode::W), Some(0x5));
+}
+
+
+#[test]
+fn test_rom_override() {
+    let config: Config = toml::from_str(r#"
+        [keys]
+        5 = ["Up"]
+        [roms.0123abcd.keys]
+        5 = ["W"]
+        6 = ["Q"]
+    "#).unwrap();
+    let defaults = default_key_bindings();
+    let rom = config.rom("0123abcd").unwrap();
+    let keymap = Bindings::new(&[&defaults, &config.keys, &rom.keys], scancode, &HOTKEYS);
+    assert_eq!(keymap.key(Scancode::W), Some(0x5));
+    assert_eq!(keymap.key(Scancode::Up), None);
+    assert_eq!(keymap.key(Scancode::Q), Some(0x6));
+    assert_eq!(keymap.key(Scancode::Num1), Some(0x1));
+    // other roms get the global layer
+    assert!(config.rom("ffff").is_none());
+    let keymap = Bindings::new(&[&defaults, &config.keys], scancode, &HOTKEYS);
+    assert_eq!(keymap.key(Scancode::Up), Some(0x5));
+}
+
+
+#[test]
+fn test_pad_input_names() {
+    assert_eq!(PadInput::from_name("a"), Some(PadInput::Button(Button::A)));
+    assert_eq!(PadInput::from_name("dpup"), Some(PadInput::Button(Button::DPadUp)));
+    assert_eq!(PadInput::from_name("leftx-"), Some(PadInput::Axis(Axis::LeftX, false)));
+    assert_eq!(PadInput::from_name("lefty+"), Some(PadInput::Axis(Axis::LeftY, true)));
+    // a trigger only goes one way
+    assert_eq!(PadInput::from_name("righttrigger"), Some(PadInput::Axis(Axis::TriggerRight, true)));
+    assert_eq!(PadInput::from_name("dpup+"), None);
+    assert_eq!(PadInput::from_name("turbo"), None);
+}
+
+
+#[test]
+fn test_axis_directions() {
+    let pressed = |value| {
+        let [(positive, positive_pushed), (negative, negative_pushed)] = axis_directions(Axis::LeftX, value);
+        assert_eq!((positive, negative), (PadInput::Axis(Axis::LeftX, true), PadInput::Axis(Axis::LeftX, false)));
+        (positive_pushed, negative_pushed)
+    };
+    assert_eq!(pressed(0), (false, false));
+    assert_eq!(pressed(AXIS_THRESHOLD), (false, false));
+    assert_eq!(pressed(AXIS_THRESHOLD + 1), (true, false));
+    assert_eq!(pressed(i16::MAX), (true, false));
+    assert_eq!(pressed(-AXIS_THRESHOLD), (false, false));
+    assert_eq!(pressed(-AXIS_THRESHOLD - 1), (false, true));
+    assert_eq!(pressed(i16::MIN), (false, true));
+}
+
+
+#[test]
+fn test_pad_map() {
+    let defaults = default_controller_bindings();
+    let padmap = PadMap::for_controller(&[&defaults]);
+    assert_eq!(padmap.key(PadInput::Button(Button::DPadUp)), Some(0x5));
+    assert_eq!(padmap.key(PadInput::Axis(Axis::LeftY, false)), Some(0x5));
+    assert_eq!(padmap.key(PadInput::Axis(Axis::LeftY, true)), Some(0x8));
+    assert_eq!(padmap.key(PadInput::Axis(Axis::LeftX, true)), Some(0x9));
+    assert_eq!(padmap.key(PadInput::Button(Button::Start)), None);
+    // the database's hints move the directions, leaving the rest
+    let hints = [("up".to_string(), 2), ("a".to_string(), 0xc)].iter().cloned().collect();
+    let hinted = controller_bindings_from_hints(&hints);
+    let padmap = PadMap::for_controller(&[&defaults, &hinted]);
+    assert_eq!(padmap.key(PadInput::Button(Button::DPadUp)), Some(0x2));
+    assert_eq!(padmap.key(PadInput::Axis(Axis::LeftY, false)), Some(0x2));
+    assert_eq!(padmap.key(PadInput::Button(Button::A)), Some(0xc));
+    assert_eq!(padmap.key(PadInput::Button(Button::DPadDown)), Some(0x8));
+}
@@ -0,0 +1,264 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use sdl2::keyboard::Keycode;
+use chippy8::cartridge::{is_rom_file, Cartridge, CartridgeError};
+use crate::config::Config;
+use crate::database::RomDatabase;
+use crate::display::{TextLine, TextStyle};
+
+// how far below rom_dir to look for roms
+const MAX_DEPTH: usize = 4;
+// how far PageUp and PageDown move
+const PAGE: usize = 10;
+const HELP: &str = "up/down: choose  enter: play  esc: quit";
+
+pub enum Choice {
+    // a rom file, and for zips of several the entry picked
+    Rom(PathBuf, Option<String>),
+    Quit,
+}
+
+enum Item {
+    Heading(String),
+    Rom { title: String, path: PathBuf, entry: Option<String> },
+}
+
+// the menu shown when chippy8 starts without a rom: recently played roms,
+// then everything under the configured rom_dir
+pub struct Launcher {
+    items: Vec<Item>,
+    selected: usize,
+    // the full list and its selection while a zip's roms are shown
+    parent: Option<(Vec<Item>, usize)>,
+    message: Option<String>,
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().unwrap_or_default().to_string_lossy().into_owned()
+}
+
+// the database's title where there is one, otherwise the file name
+fn title(path: &Path, entry: Option<&str>, database: Option<&RomDatabase>) -> String {
+    match Cartridge::new(path, entry) {
+        Ok(cart) => database
+            .and_then(|database| database.lookup(&cart.sha1))
+            .map(|settings| settings.title)
+            .unwrap_or_else(|| match entry {
+                Some(entry) => file_name(Path::new(entry)),
+                None => file_name(path),
+            }),
+        Err(CartridgeError::SeveralRoms(names)) => format!("{} ({} roms)", file_name(path), names.len()),
+        Err(_) => file_name(path),
+    }
+}
+
+// titles already worked out, by file and zip entry, with the file's
+// modification time then. the launcher comes back after every game, and
+// reading, unpacking and hashing every rom each time soon adds up.
+#[derive(Default)]
+pub struct Titles {
+    known: HashMap<(PathBuf, Option<String>), (SystemTime, String)>,
+}
+
+impl Titles {
+    fn get(&mut self, path: &Path, entry: Option<&str>, database: Option<&RomDatabase>) -> String {
+        let modified = fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+        let key = (path.to_path_buf(), entry.map(String::from));
+        if let (Some(modified), Some((known_modified, title))) = (modified, self.known.get(&key)) {
+            if *known_modified == modified {
+                return title.clone();
+            }
+        }
+        let title = title(path, entry, database);
+        if let Some(modified) = modified {
+            self.known.insert(key, (modified, title.clone()));
+        }
+        title
+    }
+}
+
+fn find_roms(dir: &Path, depth: usize, roms: &mut Vec<PathBuf>) {
+    let mut paths: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries.filter_map(|entry| Some(entry.ok()?.path())).collect(),
+        Err(e) => {
+            println!("unable to list roms in {}: {}", dir.display(), e);
+            return;
+        }
+    };
+    paths.sort();
+    for path in paths {
+        if path.is_dir() {
+            if depth < MAX_DEPTH {
+                find_roms(&path, depth + 1, roms);
+            }
+        } else if is_rom_file(&path) {
+            roms.push(path);
+        }
+    }
+}
+
+impl Launcher {
+    pub fn new(config: &Config, database: Option<&RomDatabase>, titles: &mut Titles) -> Self {
+        let mut items = Vec::new();
+        let recent: Vec<&PathBuf> = config.recent.iter().filter(|path| path.is_file()).collect();
+        if !recent.is_empty() {
+            items.push(Item::Heading("recent".to_string()));
+            for path in recent {
+                items.push(Item::Rom { title: titles.get(path, None, database), path: path.clone(), entry: None });
+            }
+        }
+        match &config.rom_dir {
+            Some(dir) => {
+                let mut roms = Vec::new();
+                find_roms(dir, 0, &mut roms);
+                items.push(Item::Heading(dir.display().to_string()));
+                if roms.is_empty() {
+                    items.push(Item::Heading("  no roms found".to_string()));
+                }
+                for path in roms {
+                    items.push(Item::Rom { title: titles.get(&path, None, database), path, entry: None });
+                }
+            }
+            None => {
+                items.push(Item::Heading("set rom_dir in the config to list roms here,".to_string()));
+                items.push(Item::Heading("or start chippy8 with a rom".to_string()));
+            }
+        }
+        let mut launcher = Launcher { items, selected: 0, parent: None, message: None };
+        launcher.selected = launcher.nearest_rom(0);
+        launcher
+    }
+
+    // shown in place of the help line until the next key
+    pub fn set_message(&mut self, message: String) {
+        self.message = Some(message);
+    }
+
+    // lists the roms of a zip that holds several, until escape goes back
+    pub fn show_archive(&mut self, path: &Path, entries: Vec<String>, database: Option<&RomDatabase>, titles: &mut Titles) {
+        let mut items = vec![Item::Heading(file_name(path))];
+        for entry in entries {
+            let title = titles.get(path, Some(&entry), database);
+            items.push(Item::Rom { title, path: path.to_path_buf(), entry: Some(entry) });
+        }
+        let parent = std::mem::replace(&mut self.items, items);
+        self.parent = Some((parent, self.selected));
+        self.selected = self.nearest_rom(0);
+    }
+
+    fn is_rom(&self, index: usize) -> bool {
+        matches!(self.items.get(index), Some(Item::Rom { .. }))
+    }
+
+    // the first rom at or after index, or failing that the last one before
+    fn nearest_rom(&self, index: usize) -> usize {
+        (index..self.items.len())
+            .chain((0..index.min(self.items.len())).rev())
+            .find(|&i| self.is_rom(i))
+            .unwrap_or(0)
+    }
+
+    fn move_by(&mut self, steps: usize, down: bool) {
+        let mut selected = self.selected;
+        for _ in 0..steps {
+            let next = if down {
+                (selected + 1..self.items.len()).find(|&i| self.is_rom(i))
+            } else {
+                (0..selected).rev().find(|&i| self.is_rom(i))
+            };
+            match next {
+                Some(next) => selected = next,
+                None => break,
+            }
+        }
+        self.selected = selected;
+    }
+
+    // the next rom after the selection whose title starts with the letter
+    fn jump_to(&mut self, letter: char) {
+        let count = self.items.len();
+        let found = (1..=count)
+            .map(|offset| (self.selected + offset) % count)
+            .find(|&i| match &self.items[i] {
+                Item::Rom { title, .. } => title.chars().next()
+                    .map(|first| first.eq_ignore_ascii_case(&letter))
+                    .unwrap_or(false),
+                Item::Heading(_) => false,
+            });
+        if let Some(index) = found {
+            self.selected = index;
+        }
+    }
+
+    pub fn key(&mut self, keycode: Keycode) -> Option<Choice> {
+        self.message = None;
+        match keycode {
+            Keycode::Up => self.move_by(1, false),
+            Keycode::Down => self.move_by(1, true),
+            Keycode::PageUp => self.move_by(PAGE, false),
+            Keycode::PageDown => self.move_by(PAGE, true),
+            Keycode::Home => self.selected = self.nearest_rom(0),
+            Keycode::End => self.move_by(self.items.len(), true),
+            Keycode::Return | Keycode::KpEnter => {
+                if let Some(Item::Rom { path, entry, .. }) = self.items.get(self.selected) {
+                    return Some(Choice::Rom(path.clone(), entry.clone()));
+                }
+            }
+            Keycode::Escape | Keycode::Backspace => match self.parent.take() {
+                Some((items, selected)) => {
+                    self.items = items;
+                    self.selected = selected;
+                }
+                None if keycode == Keycode::Escape => return Some(Choice::Quit),
+                None => {}
+            },
+            // letter and digit keycodes are their ascii codes
+            _ => {
+                let code = keycode as i32;
+                if (0..0x80).contains(&code) && (code as u8).is_ascii_alphanumeric() {
+                    self.jump_to(code as u8 as char);
+                }
+            }
+        }
+        None
+    }
+
+    // the menu for a window of the given size, scrolled to keep the
+    // selection in view
+    pub fn lines(&self, columns: usize, rows: usize) -> Vec<TextLine> {
+        let mut lines = vec![
+            TextLine::new("CHIPPY8", TextStyle::Dim),
+            TextLine::new("", TextStyle::Normal),
+        ];
+        let list_rows = rows.saturating_sub(lines.len() + 2);
+        let top = self.selected
+            .saturating_sub(list_rows / 2)
+            .min(self.items.len().saturating_sub(list_rows));
+        for (index, item) in self.items.iter().enumerate().skip(top).take(list_rows) {
+            lines.push(match item {
+                Item::Heading(text) => TextLine::new(text.as_str(), TextStyle::Dim),
+                Item::Rom { title, .. } if index == self.selected => TextLine::new(format!("  {}", title), TextStyle::Highlighted),
+                Item::Rom { title, .. } => TextLine::new(format!("  {}", title), TextStyle::Normal),
+            });
+        }
+        while lines.len() < rows.saturating_sub(1) {
+            lines.push(TextLine::new("", TextStyle::Normal));
+        }
+        match &self.message {
+            Some(message) => lines.push(TextLine::new(message.as_str(), TextStyle::Normal)),
+            None => lines.push(TextLine::new(HELP, TextStyle::Dim)),
+        }
+        for line in lines.iter_mut() {
+            if let Some((cut, _)) = line.text.char_indices().nth(columns) {
+                line.text.truncate(cut);
+            }
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+#[path = "./launcher_tests.rs"]
+mod launcher_tests;
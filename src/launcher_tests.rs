@@ -0,0 +1,39 @@
+use super::*;
+use std::env;
+use std::fs::File;
+use std::io::{Cursor, Write};
+use std::process;
+use std::time::Duration;
+
+fn zip(names: &[&str]) -> Vec<u8> {
+    let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    for name in names {
+        writer.start_file(*name, zip::write::FileOptions::default()).unwrap();
+        writer.write_all(&[0x12, 0x00]).unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+fn set_modified(path: &Path, modified: SystemTime) {
+    File::options().write(true).open(path).unwrap().set_modified(modified).unwrap();
+}
+
+
+#[test]
+fn test_titles() {
+    let path = env::temp_dir().join(format!("chippy8-launcher-{}-games.zip", process::id()));
+    let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+    fs::write(&path, zip(&["a.ch8", "b.ch8"])).unwrap();
+    set_modified(&path, modified);
+    let mut titles = Titles::default();
+    let name = file_name(&path);
+    assert_eq!(titles.get(&path, None, None), format!("{} (2 roms)", name));
+    // the same modification time, so the file isn't opened again
+    fs::write(&path, zip(&["a.ch8"])).unwrap();
+    set_modified(&path, modified);
+    assert_eq!(titles.get(&path, None, None), format!("{} (2 roms)", name));
+    set_modified(&path, modified + Duration::from_secs(1));
+    assert_eq!(titles.get(&path, None, None), name);
+    assert_eq!(titles.get(&path, Some("a.ch8"), None), "a.ch8");
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,11 @@
+// the emulator core, without SDL: what the chippy8 binary is built on, and
+// what the integration tests under tests/ drive headlessly
+pub mod analyser;
+pub mod cartridge;
+pub mod cpu;
+pub mod disasm;
+pub mod fonts;
+pub mod lockstep;
+pub mod quirks;
+pub mod reference;
+pub mod script;
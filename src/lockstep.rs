@@ -0,0 +1,176 @@
+// runs two machines on the same rom and input an instruction at a time,
+// comparing their whole state after each, to find the first instruction
+// two quirk presets, or Cpu and the reference interpreter, disagree on.
+//
+// both run a flat number of instructions per frame, so the vblank quirk
+// and COSMAC VIP timing, which only change when instructions run, aren't
+// compared.
+use std::collections::VecDeque;
+use crate::cpu::{Cpu, DISPLAY_HEIGHT, DISPLAY_WIDTH, MAX_INSTRUCTIONS_PER_FRAME, RAM_LENGTH};
+use crate::disasm::disassemble;
+use crate::script::{self, Press};
+
+// how many instructions before the divergence are shown
+const TRACE_LENGTH: usize = 8;
+// how many differing ram bytes are listed
+const MAX_RAM_DIFFERENCES: usize = 16;
+
+// everything a program can see or change
+#[derive(Clone, PartialEq)]
+pub struct Snapshot {
+    pub pc: u16,
+    pub v: [u8; 16],
+    pub i: u16,
+    pub sp: u8,
+    pub stack: [u16; 16],
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    // the register LD Vx, K is waiting to fill
+    pub waiting: Option<u8>,
+    pub ram: [u8; RAM_LENGTH],
+    pub vram: [[u8; DISPLAY_WIDTH]; DISPLAY_HEIGHT],
+}
+
+impl Snapshot {
+    pub fn opcode(&self) -> u16 {
+        let pc = self.pc as usize;
+        u16::from_be_bytes([self.ram[pc % RAM_LENGTH], self.ram[(pc + 1) % RAM_LENGTH]])
+    }
+}
+
+pub trait Machine {
+    // one instruction, or nothing while waiting for a key
+    fn step(&mut self, keys: &[bool; 16]);
+    // the 60 Hz timer tick
+    fn end_frame(&mut self);
+    fn snapshot(&self) -> Snapshot;
+}
+
+impl Machine for Cpu {
+    fn step(&mut self, keys: &[bool; 16]) {
+        self.tick(keys);
+    }
+
+    fn end_frame(&mut self) {
+        self.tick_timers();
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Cpu::snapshot(self)
+    }
+}
+
+pub struct Divergence {
+    // counting from 1, with 0 for a difference before anything ran
+    pub instruction: u64,
+    pub frame: u32,
+    // addresses and opcodes of the last instructions run, the one that
+    // diverged last
+    pub trace: Vec<(u16, u16)>,
+    pub left: Snapshot,
+    pub right: Snapshot,
+}
+
+// None if the two agree for all the frames
+pub fn run(left: &mut dyn Machine, right: &mut dyn Machine, presses: &[Press],
+           frames: u32, instructions_per_frame: u32) -> Option<Divergence> {
+    // as Cpu takes it, so 0 still runs something
+    let instructions_per_frame = instructions_per_frame.clamp(1, MAX_INSTRUCTIONS_PER_FRAME);
+    let mut trace = VecDeque::with_capacity(TRACE_LENGTH);
+    let mut instruction = 0;
+    let mut before = left.snapshot();
+    if before != right.snapshot() {
+        return Some(Divergence { instruction, frame: 0, trace: vec![], left: before, right: right.snapshot() });
+    }
+    for frame in 0..frames {
+        let keys = script::keys(presses, frame);
+        for _ in 0..instructions_per_frame {
+            if trace.len() == TRACE_LENGTH {
+                trace.pop_front();
+            }
+            trace.push_back((before.pc, before.opcode()));
+            left.step(&keys);
+            right.step(&keys);
+            instruction += 1;
+            let (after_left, after_right) = (left.snapshot(), right.snapshot());
+            if after_left != after_right {
+                let trace = trace.into_iter().collect();
+                return Some(Divergence { instruction, frame, trace, left: after_left, right: after_right });
+            }
+            before = after_left;
+        }
+        left.end_frame();
+        right.end_frame();
+        before = left.snapshot();
+        if before != right.snapshot() {
+            let trace = trace.into_iter().collect();
+            return Some(Divergence { instruction, frame, trace, left: before, right: right.snapshot() });
+        }
+    }
+    None
+}
+
+fn row(name: &str, left: String, right: String) -> String {
+    let mark = if left != right { "<" } else { "" };
+    format!("{:<10}{:<18}{:<18}{}", name, left, right, mark)
+}
+
+fn screen_row(row: &[u8; DISPLAY_WIDTH]) -> String {
+    row.iter().map(|&pixel| if pixel != 0 { '#' } else { '.' }).collect()
+}
+
+impl Divergence {
+    // the instructions leading up to it, then the two states side by side
+    // with the differences marked
+    pub fn report(&self, left_name: &str, right_name: &str) -> Vec<String> {
+        let mut lines = vec![format!("diverged at instruction {}, in frame {}", self.instruction, self.frame)];
+        for (index, &(pc, opcode)) in self.trace.iter().enumerate() {
+            let marker = if index + 1 == self.trace.len() { '>' } else { ' ' };
+            lines.push(format!("{} {:03X}: {:04X}  {}", marker, pc, opcode, disassemble(opcode)));
+        }
+        lines.push(String::new());
+        let (left, right) = (&self.left, &self.right);
+        lines.push(format!("{:<10}{:<18}{}", "", left_name, right_name));
+        lines.push(row("pc", format!("{:03X}", left.pc), format!("{:03X}", right.pc)));
+        for register in 0..16 {
+            lines.push(row(&format!("v{:x}", register),
+                           format!("{:02X}", left.v[register]),
+                           format!("{:02X}", right.v[register])));
+        }
+        lines.push(row("i", format!("{:03X}", left.i), format!("{:03X}", right.i)));
+        lines.push(row("sp", left.sp.to_string(), right.sp.to_string()));
+        for entry in 0..16 {
+            if left.stack[entry] != right.stack[entry] || entry <= left.sp.max(right.sp) as usize {
+                lines.push(row(&format!("stack[{}]", entry),
+                               format!("{:03X}", left.stack[entry]),
+                               format!("{:03X}", right.stack[entry])));
+            }
+        }
+        lines.push(row("dt", left.delay_timer.to_string(), right.delay_timer.to_string()));
+        lines.push(row("st", left.sound_timer.to_string(), right.sound_timer.to_string()));
+        let waiting = |waiting: Option<u8>| waiting.map(|x| format!("key to v{:x}", x)).unwrap_or_else(|| "-".to_string());
+        lines.push(row("waiting", waiting(left.waiting), waiting(right.waiting)));
+        let ram: Vec<usize> = (0..RAM_LENGTH).filter(|&addr| left.ram[addr] != right.ram[addr]).collect();
+        for &addr in ram.iter().take(MAX_RAM_DIFFERENCES) {
+            lines.push(row(&format!("ram[{:03X}]", addr),
+                           format!("{:02X}", left.ram[addr]),
+                           format!("{:02X}", right.ram[addr])));
+        }
+        if ram.len() > MAX_RAM_DIFFERENCES {
+            lines.push(format!("and {} more ram bytes differ", ram.len() - MAX_RAM_DIFFERENCES));
+        }
+        if left.vram != right.vram {
+            lines.push(String::new());
+            lines.push(format!("{:<w$} | {}", left_name, right_name, w = DISPLAY_WIDTH));
+            for (left_row, right_row) in left.vram.iter().zip(right.vram.iter()) {
+                let mark = if left_row != right_row { " <" } else { "" };
+                lines.push(format!("{} | {}{}", screen_row(left_row), screen_row(right_row), mark));
+            }
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+#[path = "./lockstep_tests.rs"]
+mod lockstep_tests;
@@ -0,0 +1,35 @@
+use super::*;
+use crate::quirks::{Quirks, ORIGINAL_CHIP8, SUPERCHIP};
+
+// 200: LD V2, 3   202: SHR V1, V2   204: JP 204
+const ROM: [u8; 6] = [0x62, 0x03, 0x81, 0x26, 0x12, 0x04];
+
+fn cpu(quirks: Quirks) -> Cpu {
+    let mut cpu = Cpu::new();
+    cpu.load_rom(&ROM).unwrap();
+    cpu.set_quirks(quirks);
+    cpu
+}
+
+
+#[test]
+fn test_divergence() {
+    let (mut left, mut right) = (cpu(Quirks::default()), cpu(SUPERCHIP));
+    assert!(run(&mut left, &mut right, &[], 10, 10).is_none());
+    let (mut left, mut right) = (cpu(Quirks::default()), cpu(ORIGINAL_CHIP8));
+    let divergence = run(&mut left, &mut right, &[], 10, 10).unwrap();
+    assert_eq!(divergence.instruction, 2);
+    assert_eq!(divergence.frame, 0);
+    assert_eq!(divergence.trace, vec![(0x200, 0x6203), (0x202, 0x8126)]);
+    assert_eq!((divergence.left.v[1], divergence.right.v[1]), (0, 1));
+}
+
+
+#[test]
+fn test_no_instructions_per_frame() {
+    // taken as 1, as Cpu takes it, rather than running nothing
+    let (mut left, mut right) = (cpu(Quirks::default()), cpu(ORIGINAL_CHIP8));
+    let divergence = run(&mut left, &mut right, &[], 2, 0).unwrap();
+    assert_eq!(divergence.instruction, 2);
+    assert_eq!(divergence.frame, 1);
+}
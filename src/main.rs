@@ -1,68 +1,979 @@
-mod cartridge;
+mod capture;
+mod cli;
+mod config;
+mod database;
 mod input;
+mod launcher;
+mod osd;
 mod display;
-mod fonts;
-mod cpu;
+mod pacer;
+mod palette;
+mod persistence;
 mod sound;
+mod savestate;
+mod stepping;
+mod watch;
+mod wav;
 
 use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::process;
 use std::time::Instant;
-use std::time::Duration;
-use crate::cartridge::Cartridge;
-use crate::input::Input;
-use crate::display::Display;
-use crate::cpu::Cpu;
-use crate::sound::Sound;
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use chippy8::{analyser, disasm, lockstep, script};
+use crate::capture::{Recorder, DEFAULT_CAPTURE_SCALE};
+use chippy8::cartridge::{Cartridge, CartridgeError, Platform};
+use crate::cli::{Command, Options, Side, USAGE};
+use crate::config::{Config, RomConfig, WindowConfig, controller_bindings_from_hints, default_controller_bindings, default_key_bindings};
+use crate::database::{RomDatabase, RomSettings};
+use crate::input::{Input, KeyMap, PadMap};
+use crate::launcher::{Choice, Launcher, Titles};
+use crate::osd::Osd;
+use crate::display::{Display, Scaling};
+use chippy8::cpu::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use crate::pacer::{FpsMeter, Pacer, Pacing};
+use crate::palette::Palette;
+use crate::persistence::{Persistence, DEFAULT_FADE_FRAMES};
+use chippy8::cpu::{Cpu, TimingMode, DEFAULT_INSTRUCTIONS_PER_FRAME, MAX_INSTRUCTIONS_PER_FRAME, RESET_VECTOR};
+use chippy8::reference::Reference;
+use crate::savestate::SaveStates;
+use crate::sound::{Sound, Tone, Waveform};
+use crate::stepping::Stepping;
+use crate::watch::RomWatcher;
+use crate::wav::WavWriter;
+use sdl2::EventPump;
+use sdl2::GameControllerSubsystem;
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::{Keycode, Mod};
+
+// instructions per frame offered by the speed hotkeys
+const SPEED_STEPS: [u32; 16] = [1, 2, 3, 5, 7, 10, 15, 20, 30, 50, 100, 200, 500, 1000, 5000, 10000];
+
+fn faster(instructions_per_frame: u32) -> u32 {
+    SPEED_STEPS.iter()
+        .copied()
+        .find(|&step| step > instructions_per_frame)
+        .unwrap_or(instructions_per_frame)
+}
+
+fn slower(instructions_per_frame: u32) -> u32 {
+    SPEED_STEPS.iter()
+        .rev()
+        .copied()
+        .find(|&step| step < instructions_per_frame)
+        .unwrap_or(instructions_per_frame)
+}
+
+fn speed(instructions_per_frame: u32) -> String {
+    format!("speed: {} ips, {} per frame", instructions_per_frame * 60, instructions_per_frame)
+}
+
+fn window_title(title: &str, fps: Option<f64>, stepping: &Stepping) -> String {
+    let mut text = format!("CHIPPY8 - {}", title);
+    if stepping.is_paused() {
+        text.push_str(" - paused");
+        return text;
+    }
+    if let Some(fps) = fps {
+        text.push_str(&format!(" - {:.1} fps", fps));
+    }
+    if stepping.is_fast() {
+        text.push_str(" - fast forward");
+    } else if stepping.slow_motion() > 1 {
+        text.push_str(&format!(" - slow motion {}%", 100 / stepping.slow_motion()));
+    }
+    text
+}
+
+// how much each press of the volume hotkeys changes the volume, out of 1.0
+const VOLUME_STEP: f32 = 0.05;
+
+// the save state slot of a digit key; digit keycodes are their ascii codes
+fn state_slot(keycode: Keycode) -> Option<u32> {
+    let code = keycode as i32;
+    if (0x30..0x3A).contains(&code) {
+        Some((code - 0x30) as u32)
+    } else {
+        None
+    }
+}
+
+fn load_database(config: &Config) -> Option<RomDatabase> {
+    let dir = config.database_dir()?;
+    if !dir.is_dir() {
+        return None;
+    }
+    RomDatabase::load(&dir)
+        .map_err(|e| println!("unable to load rom database: {}", e))
+        .ok()
+}
+
+// unknown roms, or no database at all, just get the defaults
+fn lookup_rom(database: Option<&RomDatabase>, sha1: &str) -> Option<RomSettings> {
+    database?.lookup(sha1)
+}
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    let rom_file = &args[1];
-    let cart = Cartridge::new(rom_file).expect("file not found");
+    let args: Vec<String> = env::args().skip(1).collect();
+    let command = match cli::parse(&args) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("error: {}\nrun 'chippy8 --help' for usage", e);
+            process::exit(2);
+        }
+    };
+    let result = match command {
+        Command::Help => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        Command::Run(options) => run(&options),
+        Command::Disasm(options) => disasm(&options),
+        Command::Info(options) => info(&options),
+        Command::Bench(options) => bench(&options),
+        Command::Diff(options) => diff(&options),
+    };
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn rom_error(path: &Path, e: CartridgeError) -> String {
+    format!("cannot load rom {}: {}", path.display(), e)
+}
+
+// the rom to run from a zip of several, asked for on the terminal
+fn pick_entry(names: &[String]) -> Option<String> {
+    if !io::stdin().is_terminal() {
+        return None;
+    }
+    println!("{} roms in the archive:", names.len());
+    for (n, name) in names.iter().enumerate() {
+        println!("{:4}  {}", n + 1, name);
+    }
+    loop {
+        print!("rom to run (number or name, empty to give up): ");
+        io::stdout().flush().ok()?;
+        let mut line = String::new();
+        io::stdin().read_line(&mut line).ok()?;
+        let answer = line.trim();
+        if answer.is_empty() {
+            return None;
+        }
+        match answer.parse::<usize>() {
+            Ok(n) if (1..=names.len()).contains(&n) => return Some(names[n - 1].clone()),
+            Ok(_) => println!("there's no rom {}", answer),
+            Err(_) => return Some(answer.to_string()),
+        }
+    }
+}
+
+fn open_cartridge(path: &Path, entry: Option<&str>) -> Result<Cartridge, CartridgeError> {
+    let cart = match Cartridge::new(path, entry) {
+        Err(CartridgeError::SeveralRoms(names)) if entry.is_none() => match pick_entry(&names) {
+            Some(entry) => Cartridge::new(path, Some(&entry)),
+            None => Err(CartridgeError::SeveralRoms(names)),
+        },
+        result => result,
+    }?;
+    for warning in &cart.warnings {
+        println!("warning: {}: {}", path.display(), warning);
+    }
+    Ok(cart)
+}
+
+// the rom given on the command line, and its path
+fn open_rom(options: &Options) -> Result<(&Path, Cartridge), String> {
+    let path = options.rom.as_deref().ok_or("no rom given")?;
+    let cart = open_cartridge(path, options.entry.as_deref()).map_err(|e| rom_error(path, e))?;
+    Ok((path, cart))
+}
+
+fn load_config(options: &Options) -> Config {
+    match &options.config {
+        Some(path) => Config::load_from(path),
+        None => Config::load(),
+    }
+}
+
+// the database's platform for the rom, or failing that a guess from its code
+fn rom_platform(cart: &Cartridge, rom_settings: Option<&RomSettings>) -> Platform {
+    rom_settings
+        .and_then(|settings| settings.platform.as_deref())
+        .map(Platform::from_database_id)
+        .unwrap_or(cart.platform)
+}
+
+// database settings first, then the rom's section of the config, then
+// anything given on the command line
+fn setup_cpu(options: &Options, cart: &Cartridge, rom_settings: Option<&RomSettings>, rom_config: Option<&RomConfig>) -> Result<Cpu, CartridgeError> {
+    cart.check_fits(rom_platform(cart, rom_settings))?;
     let mut cpu = Cpu::new();
-    cpu.load_rom(&cart.rom);
-
-    println!("initializing sdl2");
-    let sdl = sdl2::init().unwrap();
-    let mut input = Input::new();
-    println!("creating window");
-    let scale_xy = 16;
-    let mut display = Display::new(&sdl, scale_xy);
-    let mut sound = Sound::new(&sdl);
-    let mut event_pump = sdl.event_pump().unwrap();
-    let target_time = Duration::from_millis(1000 / 60);
-    println!("starting game loop");
-    'game_loop: loop {
-        let time_before = Instant::now();
-        // handle events like key presses and window resizing/closing
-        for event in event_pump.poll_iter() {
+    cpu.load_rom(&cart.rom)?;
+    if let Some(settings) = rom_settings {
+        if let Some(quirks) = settings.quirks {
+            cpu.set_quirks(quirks);
+        }
+        if let Some(tickrate) = settings.tickrate {
+            cpu.set_instructions_per_frame(tickrate);
+        }
+    }
+    if let Some(instructions_per_frame) = rom_config.and_then(|rom| rom.instructions_per_frame) {
+        cpu.set_instructions_per_frame(instructions_per_frame);
+    }
+    if let Some(quirks) = options.quirks {
+        cpu.set_quirks(quirks);
+    }
+    if let Some(instructions_per_frame) = options.instructions_per_frame {
+        cpu.set_instructions_per_frame(instructions_per_frame);
+    }
+    if options.vip {
+        cpu.set_timing_mode(TimingMode::CosmacVip);
+    }
+    if let Some(seed) = options.seed {
+        cpu.set_seed(seed);
+    }
+    Ok(cpu)
+}
+
+// the configured palette and colours, then the rom's colours from the
+// database, then --fg and --bg
+fn initial_palette(options: &Options, config: &Config, rom_settings: Option<&RomSettings>) -> Palette {
+    let mut palette = match &config.display.palette {
+        None => Palette::default(),
+        Some(name) => Palette::named(name).unwrap_or_else(|| {
+            let names: Vec<_> = Palette::names().collect();
+            println!("unknown palette '{}', expected one of {}", name, names.join(", "));
+            Palette::default()
+        }),
+    };
+    palette = palette.with_hex_colors(&config.display.colors);
+    if let Some(settings) = rom_settings {
+        palette = palette.with_hex_colors(&settings.colors);
+    }
+    if let Some(bg) = options.bg {
+        palette.colors[0] = bg;
+    }
+    if let Some(fg) = options.fg {
+        palette.colors[1] = fg;
+    }
+    palette
+}
+
+// the [sound] config, then --volume and --mute
+fn initial_tone(options: &Options, config: &Config) -> Tone {
+    let sound = &config.sound;
+    let mut tone = Tone::default();
+    if let Some(frequency) = sound.frequency {
+        tone.frequency = frequency;
+    }
+    if let Some(name) = &sound.waveform {
+        match Waveform::from_name(name) {
+            Some(waveform) => tone.waveform = waveform,
+            None => println!("unknown waveform '{}', expected square, triangle, sine or noise", name),
+        }
+    }
+    if let Some(volume) = sound.volume {
+        tone.volume = (volume / 100.0).clamp(0.0, 1.0);
+    }
+    if let Some(attack_ms) = sound.attack_ms {
+        tone.attack = attack_ms / 1000.0;
+    }
+    if let Some(release_ms) = sound.release_ms {
+        tone.release = release_ms / 1000.0;
+    }
+    if let Some(volume) = options.volume {
+        tone.volume = volume;
+    }
+    tone
+}
+
+fn disasm(options: &Options) -> Result<(), String> {
+    let (_, cart) = open_rom(options)?;
+    for line in disasm::listing(&cart.rom, RESET_VECTOR) {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+fn info(options: &Options) -> Result<(), String> {
+    let (path, cart) = open_rom(options)?;
+    let config = load_config(options);
+    println!("file:     {}", path.display());
+    if let Some(entry) = &cart.entry {
+        println!("entry:    {}", entry);
+    }
+    println!("size:     {} bytes", cart.size());
+    println!("sha1:     {}", cart.sha1);
+    println!("detected: {}", cart.platform);
+    let analysis = analyser::analyse(&cart.rom);
+    println!("analysis: {} instructions reached, {} bytes not reached", analysis.reached.len(), analysis.unreached_bytes);
+    if analysis.platform_opcodes.is_empty() {
+        println!("opcodes:  CHIP-8 only");
+    }
+    for (opcode, addrs) in &analysis.platform_opcodes {
+        let platform = analyser::opcode_platform(*opcode).unwrap_or(Platform::Chip8);
+        println!("opcodes:  {} {:04X} at {}", platform, opcode, analyser::addresses(addrs));
+    }
+    if !analysis.unknown_opcodes.is_empty() {
+        println!("unknown:  {}", analyser::addresses(&analysis.unknown_opcodes));
+    }
+    for (pattern, addrs) in &analysis.patterns {
+        println!("quirks:   {} at {}", pattern.describe(), analyser::addresses(addrs));
+    }
+    if analysis.patterns.contains_key(&analyser::Pattern::Jump) {
+        println!("          code reached only through Bnnn isn't analysed");
+    }
+    println!("suggests: {}, --quirks {}", analysis.platform, analysis.preset());
+    let quirks = analysis.quirks();
+    if !quirks.is_empty() {
+        let quirks: Vec<String> = quirks.iter().map(|(name, value)| format!("{}={}", name, value)).collect();
+        println!("          which sets {}", quirks.join(", "));
+    }
+    match lookup_rom(load_database(&config).as_ref(), &cart.sha1) {
+        None => println!("not in the rom database"),
+        Some(settings) => {
+            println!("title:    {}", settings.title);
+            println!("platform: {}", settings.platform.as_deref().unwrap_or("unknown"));
+            if let Some(tickrate) = settings.tickrate {
+                println!("tickrate: {}", tickrate);
+            }
+            if let Some(quirks) = settings.quirks {
+                println!("quirks:   {:?}", quirks);
+            }
+            for (hint, key) in &settings.keys {
+                println!("key:      {} = {:X}", hint, key);
+            }
+        }
+    }
+    Ok(())
+}
+
+// runs frames back to back with no window, sound or input
+fn bench(options: &Options) -> Result<(), String> {
+    let (path, cart) = open_rom(options)?;
+    let config = load_config(options);
+    let rom_settings = lookup_rom(load_database(&config).as_ref(), &cart.sha1);
+    let mut cpu = setup_cpu(options, &cart, rom_settings.as_ref(), config.rom(&cart.sha1)).map_err(|e| rom_error(path, e))?;
+    let mut wav = match &options.wav {
+        Some(path) => Some(WavWriter::create(path, initial_tone(options, &config))?),
+        None => None,
+    };
+    let frames = options.frames.unwrap_or(3600);
+    let start = Instant::now();
+    for _ in 0..frames {
+        let output = cpu.tick_60_hz(&[false; 16]);
+        if let Some(wav) = &mut wav {
+            wav.write_frame(output.beep)?;
+        }
+    }
+    let seconds = start.elapsed().as_secs_f64();
+    if let Some(wav) = wav {
+        wav.finish()?;
+    }
+    println!("{} frames, {} instructions in {:.3}s", frames, cpu.instruction_count(), seconds);
+    println!("{:.0} frames/s, {:.0} instructions/s ({:.1}x real time)",
+             frames as f64 / seconds,
+             cpu.instruction_count() as f64 / seconds,
+             frames as f64 / 60.0 / seconds);
+    Ok(())
+}
+
+fn machine(side: Side, rom: &[u8], seed: u64) -> Result<Box<dyn lockstep::Machine>, String> {
+    Ok(match side {
+        Side::Emulator(quirks) => {
+            let mut cpu = Cpu::new();
+            cpu.set_quirks(quirks);
+            cpu.set_seed(seed);
+            cpu.load_rom(rom).map_err(|e| e.to_string())?;
+            Box::new(cpu)
+        }
+        Side::Reference(quirks) => Box::new(Reference::new(rom, quirks, seed)),
+    })
+}
+
+fn side_name(side: Side) -> String {
+    match side {
+        Side::Emulator(quirks) => format!("chippy8 {}", quirks.name()),
+        Side::Reference(quirks) => format!("reference {}", quirks.name()),
+    }
+}
+
+// runs the rom on two machines in lockstep until they disagree
+fn diff(options: &Options) -> Result<(), String> {
+    let (_, cart) = open_rom(options)?;
+    let quirks = options.quirks.unwrap_or_default();
+    let left = options.left.unwrap_or(Side::Emulator(quirks));
+    let right = options.right.unwrap_or(Side::Reference(quirks));
+    let seed = options.seed.unwrap_or(0);
+    let presses = script::parse(&options.input)?;
+    let frames = options.frames.unwrap_or(600);
+    let instructions_per_frame = options.instructions_per_frame.unwrap_or(DEFAULT_INSTRUCTIONS_PER_FRAME)
+        .clamp(1, MAX_INSTRUCTIONS_PER_FRAME);
+    let mut left_machine = machine(left, &cart.rom, seed)?;
+    let mut right_machine = machine(right, &cart.rom, seed)?;
+    match lockstep::run(left_machine.as_mut(), right_machine.as_mut(), &presses, frames, instructions_per_frame) {
+        None => println!("no divergence in {} frames, {} instructions",
+                         frames, frames as u64 * instructions_per_frame as u64),
+        Some(divergence) => {
+            for line in divergence.report(&side_name(left), &side_name(right)) {
+                println!("{}", line);
+            }
+            process::exit(1);
+        }
+    }
+    Ok(())
+}
+
+
+// what ended a game
+enum Exit {
+    Quit,
+    // back to the launcher
+    Launcher,
+}
+
+// the window, sound, controllers and captures, which carry on from one
+// game to the next
+struct Session<'a> {
+    options: &'a Options,
+    config: Config,
+    database: Option<RomDatabase>,
+    display: Display,
+    osd: Osd,
+    // the launcher's, kept from one visit to the next
+    titles: Titles,
+    sound: Sound,
+    input: Input,
+    event_pump: EventPump,
+    controller_subsystem: Option<GameControllerSubsystem>,
+    // the last windowed geometry, saved on exit
+    window_geometry: ((u32, u32), (i32, i32)),
+    fade_frames: u32,
+    capture_scale: u32,
+    recorder: Option<Recorder>,
+    wav: Option<WavWriter>,
+    frames: u32,
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    // a bad rom on the command line fails before any window opens
+    let rom = match &options.rom {
+        Some(_) => Some(open_rom(options)?),
+        None => None,
+    };
+    let mut session = Session::new(options)?;
+    let result = match rom {
+        Some((path, cart)) => session.start(path, cart, false).map(|_| ()),
+        None => session.browse(),
+    };
+    session.finish()?;
+    result
+}
+
+impl<'a> Session<'a> {
+    fn new(options: &'a Options) -> Result<Self, String> {
+        let config = load_config(options);
+        let database = load_database(&config);
+
+        println!("initializing sdl2");
+        let sdl = sdl2::init()?;
+        // per-rom bindings are layered on as each game starts
+        let default_keys = default_key_bindings();
+        let default_controller = default_controller_bindings();
+        let input = Input::new(
+            KeyMap::for_keyboard(&[&default_keys, &config.keys]),
+            PadMap::for_controller(&[&default_controller, &config.controller]));
+        // controllers already plugged in show up as ControllerDeviceAdded events
+        // once the subsystem is up
+        let controller_subsystem = match sdl.game_controller() {
+            Ok(subsystem) => Some(subsystem),
+            Err(e) => {
+                println!("controllers unavailable: {}", e);
+                None
+            }
+        };
+        println!("creating window");
+        // --scale wins over the size remembered from last time
+        let window_size = match (options.scale, config.window.width, config.window.height) {
+            (None, Some(width), Some(height)) => (width, height),
+            (scale, _, _) => {
+                let scale_xy = scale.unwrap_or(16);
+                (DISPLAY_WIDTH as u32 * scale_xy, DISPLAY_HEIGHT as u32 * scale_xy)
+            }
+        };
+        let window_position = config.window.x.zip(config.window.y);
+        let mut display = Display::new(&sdl, window_size, window_position, options.fullscreen);
+        if let Some(name) = &config.display.scaling {
+            match Scaling::from_name(name) {
+                Some(scaling) => display.set_scaling(scaling),
+                None => println!("unknown scaling '{}', expected fit, integer or stretch", name),
+            }
+        }
+        let window_geometry = display.window_geometry();
+        display.set_palette(initial_palette(options, &config, None));
+        let fade_frames = config.display.fade_frames.unwrap_or(DEFAULT_FADE_FRAMES);
+        if let Some(name) = &config.display.persistence {
+            match Persistence::from_name(name, fade_frames) {
+                Some(persistence) => display.set_persistence(persistence),
+                None => println!("unknown persistence '{}', expected off, fade or max2", name),
+            }
+        }
+        let tone = initial_tone(options, &config);
+        let mut sound = Sound::new(&sdl, tone);
+        sound.set_muted(options.mute || config.sound.muted);
+        let capture_scale = options.capture_scale.unwrap_or(DEFAULT_CAPTURE_SCALE);
+        let recorder = match &options.record {
+            Some(path) => {
+                let palette = options.capture_palette.clone().unwrap_or_else(|| display.palette().clone());
+                Some(Recorder::create(path, &palette, capture_scale)?)
+            }
+            None => None,
+        };
+        // the wav gets the tone as configured, whatever the mute and volume
+        // hotkeys do to playback
+        let wav = match &options.wav {
+            Some(path) => Some(WavWriter::create(path, tone)?),
+            None => None,
+        };
+        let event_pump = sdl.event_pump()?;
+        Ok(Session {
+            options,
+            config,
+            database,
+            display,
+            osd: Osd::new(),
+            titles: Titles::default(),
+            sound,
+            input,
+            event_pump,
+            controller_subsystem,
+            window_geometry,
+            fade_frames,
+            capture_scale,
+            recorder,
+            wav,
+            frames: 0,
+        })
+    }
+
+    fn capture_palette(&self) -> Palette {
+        self.options.capture_palette.clone().unwrap_or_else(|| self.display.palette().clone())
+    }
+
+    fn toggle_fullscreen(&mut self) {
+        if !self.display.is_fullscreen() {
+            self.window_geometry = self.display.window_geometry();
+        }
+        self.display.toggle_fullscreen();
+    }
+
+    // controllers come and go whether a game is running or not; returns
+    // whether the event was one of those
+    fn hotplug(&mut self, event: &Event) -> bool {
+        match *event {
+            Event::ControllerDeviceAdded { which, .. } => {
+                if let Some(subsystem) = &self.controller_subsystem {
+                    self.input.add_controller(subsystem, which);
+                }
+                true
+            }
+            Event::ControllerDeviceRemoved { which, .. } => {
+                self.input.remove_controller(which);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // the launcher, until the window is closed or escape is pressed at
+    // the top level
+    fn browse(&mut self) -> Result<(), String> {
+        let mut launcher = Launcher::new(&self.config, self.database.as_ref(), &mut self.titles);
+        loop {
+            let (path, entry) = match self.choose(&mut launcher) {
+                Choice::Rom(path, entry) => (path, entry),
+                Choice::Quit => return Ok(()),
+            };
+            let cart = match Cartridge::new(&path, entry.as_deref()) {
+                Ok(cart) => cart,
+                Err(CartridgeError::SeveralRoms(names)) => {
+                    launcher.show_archive(&path, names, self.database.as_ref(), &mut self.titles);
+                    continue;
+                }
+                Err(e) => {
+                    launcher.set_message(e.to_string());
+                    continue;
+                }
+            };
+            if let Err(e) = self.config.add_recent(&path) {
+                println!("unable to save recent roms: {}", e);
+            }
+            match self.start(&path, cart, true) {
+                Ok(Exit::Quit) => return Ok(()),
+                Ok(Exit::Launcher) => launcher = Launcher::new(&self.config, self.database.as_ref(), &mut self.titles),
+                Err(e) => launcher.set_message(e),
+            }
+        }
+    }
+
+    fn choose(&mut self, launcher: &mut Launcher) -> Choice {
+        self.display.set_title("CHIPPY8");
+        self.display.set_palette(initial_palette(self.options, &self.config, None));
+        loop {
+            let (columns, rows) = self.display.text_grid();
+            self.display.draw_text_screen(&launcher.lines(columns, rows));
+            // nothing moves in the menu, so it only wakes up for events
+            let event = self.event_pump.wait_event();
+            if self.hotplug(&event) {
+                continue;
+            }
             match event {
-                Event::Quit {..} |
-                Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
-                    break 'game_loop
+                Event::Quit {..} => return Choice::Quit,
+                // escape held down in a game shouldn't go on to quit the launcher
+                Event::KeyDown { keycode: Some(Keycode::Escape), repeat: true, .. } => {}
+                Event::KeyDown { keycode: Some(Keycode::F11), repeat: false, .. } => self.toggle_fullscreen(),
+                Event::KeyDown { keycode: Some(keycode), .. } => {
+                    if let Some(choice) = launcher.key(keycode) {
+                        return choice;
+                    }
                 }
-                Event::KeyDown {..} => { input.keydown(event) }
-                Event::KeyUp {..} => { input.keyup(event) }
-                // TODO resize with WindowEvent::Resized(i32, i32)
                 _ => {}
             }
         }
-        // let time_before = instant::now();
-        let keys = input.keys_pressed();
-        let output = cpu.tick_60_hz(keys);
-        if output.vram_changed {
-            // println!("drawing");
-            display.draw(output.vram);
+    }
+
+    // sets up the cpu, bindings and palette for a rom and plays it. an
+    // error starting is returned; one from the captures during the game
+    // ends it
+    fn start(&mut self, path: &Path, cart: Cartridge, from_launcher: bool) -> Result<Exit, String> {
+        let options = self.options;
+        let rom_settings = lookup_rom(self.database.as_ref(), &cart.sha1);
+        if let Some(settings) = &rom_settings {
+            println!("{} ({})", settings.title, settings.platform.as_deref().unwrap_or("unknown platform"));
+            for (hint, key) in &settings.keys {
+                println!("  {}: key {:X}", hint, key);
+            }
+        }
+        let cpu = setup_cpu(options, &cart, rom_settings.as_ref(), self.config.rom(&cart.sha1)).map_err(|e| rom_error(path, e))?;
+        // already printed for a rom from the command line, but not from the
+        // launcher, and either way easy to miss behind a fullscreen window
+        for warning in &cart.warnings {
+            self.osd.show(format!("warning: {}", warning));
+        }
+
+        let config = &self.config;
+        let default_keys = default_key_bindings();
+        let default_controller = default_controller_bindings();
+        let mut key_layers = vec![&default_keys, &config.keys];
+        let mut controller_layers = vec![&default_controller, &config.controller];
+        let hinted_controller = rom_settings.as_ref()
+            .map(|settings| controller_bindings_from_hints(&settings.keys))
+            .unwrap_or_default();
+        controller_layers.push(&hinted_controller);
+        if let Some(rom_config) = config.rom(&cart.sha1) {
+            key_layers.push(&rom_config.keys);
+            controller_layers.push(&rom_config.controller);
         }
-        sound.beep(output.beep);
-        // sleep to adjust for fps
-        let sleep_millis = target_time.checked_sub(Instant::now() - time_before);
-        match sleep_millis {
-            None => {}, // we're running below target fps
-            Some(sleep_millis) => {
-                ::std::thread::sleep(sleep_millis);
+        self.input.set_bindings(
+            KeyMap::for_keyboard(&key_layers),
+            PadMap::for_controller(&controller_layers));
+        self.display.set_palette(initial_palette(options, config, rom_settings.as_ref()));
+
+        let title = match &rom_settings {
+            Some(settings) => settings.title.clone(),
+            None => {
+                let file_name = cart.entry.as_deref().map(Path::new).unwrap_or(path);
+                file_name.file_stem().unwrap_or_default().to_string_lossy().into_owned()
             }
+        };
+        let exit = self.play(cpu, path, &cart, &title, from_launcher);
+        self.sound.beep(false);
+        exit
+    }
+
+    // a fresh cpu for a rebuilt rom, with a save state or the old ram put
+    // back over it if the options ask for that; and the new rom's size
+    fn reload(&mut self, path: &Path, entry: Option<&str>, old: &Cpu, old_length: usize, states: &SaveStates) -> Result<(Cpu, usize), String> {
+        let cart = Cartridge::new(path, entry).map_err(|e| rom_error(path, e))?;
+        for warning in &cart.warnings {
+            self.osd.message(format!("warning: {}", warning));
+        }
+        let rom_settings = lookup_rom(self.database.as_ref(), &cart.sha1);
+        let mut cpu = setup_cpu(self.options, &cart, rom_settings.as_ref(), self.config.rom(&cart.sha1)).map_err(|e| rom_error(path, e))?;
+        if let Some(slot) = self.options.watch_state {
+            match states.load(slot, &mut cpu) {
+                // the state holds the program it was saved with, so the new
+                // one goes back over it
+                Ok(_) => cpu.replace_program(&cart.rom, old_length).map_err(|e| rom_error(path, e))?,
+                Err(e) => self.osd.message(format!("{}, starting from the beginning", e)),
+            }
+        } else if self.options.watch_keep_ram {
+            // the old build's code is left out too, where it ran longer
+            cpu.keep_ram(old, old_length.max(cart.size()));
+        }
+        Ok((cpu, cart.size()))
+    }
+
+    fn play(&mut self, mut cpu: Cpu, path: &Path, cart: &Cartridge, title: &str, from_launcher: bool) -> Result<Exit, String> {
+        let options = self.options;
+        let states = SaveStates::new(&self.config.state_dir(), path, cart.entry.as_deref());
+        let mut watcher = if options.watch { Some(RomWatcher::new(path, watch::POLL_INTERVAL)) } else { None };
+        let mut program_length = cart.size();
+        // the pacing the uncap hotkey goes back to
+        let capped_pacing = options.pacing.filter(|&pacing| pacing != Pacing::Uncapped).unwrap_or(Pacing::WallClock);
+        let uncapped = options.uncapped || options.pacing == Some(Pacing::Uncapped);
+        let mut pacer = Pacer::new(if uncapped { Pacing::Uncapped } else { capped_pacing });
+        let mut fps_meter = FpsMeter::new();
+        let mut stepping = Stepping::new(options.fast_forward.or(self.config.fast_forward).unwrap_or(0));
+        let mut fps = None;
+        self.display.set_title(&window_title(title, fps, &stepping));
+        self.display.draw(cpu.vram());
+        println!("starting game loop");
+        let mut instruction_count = cpu.instruction_count();
+        let exit = 'game_loop: loop {
+            let frame_start = Instant::now();
+            if watcher.as_mut().is_some_and(RomWatcher::changed) {
+                match self.reload(path, cart.entry.as_deref(), &cpu, program_length, &states) {
+                    Ok((reloaded, length)) => {
+                        self.osd.message(format!("reloaded {}", path.file_name().unwrap_or_default().to_string_lossy()));
+                        cpu = reloaded;
+                        program_length = length;
+                        self.display.draw(cpu.vram());
+                    }
+                    Err(e) => self.osd.message(format!("{}, carrying on with the old build", e)),
+                }
+            }
+            // handle events like key presses and window resizing/closing
+            let events: Vec<Event> = self.event_pump.poll_iter().collect();
+            for event in events {
+                if self.hotplug(&event) {
+                    continue;
+                }
+                match event {
+                    Event::Quit {..} => break 'game_loop Exit::Quit,
+                    Event::KeyDown { keycode: Some(Keycode::Escape), repeat: false, .. } => {
+                        break 'game_loop if from_launcher { Exit::Launcher } else { Exit::Quit };
+                    }
+                    Event::KeyDown { keycode: Some(Keycode::PageUp), .. } => {
+                        cpu.set_timing_mode(TimingMode::InstructionsPerFrame);
+                        cpu.set_instructions_per_frame(faster(cpu.instructions_per_frame()));
+                        self.osd.message(speed(cpu.instructions_per_frame()));
+                    }
+                    Event::KeyDown { keycode: Some(Keycode::PageDown), .. } => {
+                        cpu.set_timing_mode(TimingMode::InstructionsPerFrame);
+                        cpu.set_instructions_per_frame(slower(cpu.instructions_per_frame()));
+                        self.osd.message(speed(cpu.instructions_per_frame()));
+                    }
+                    Event::KeyDown { keycode: Some(Keycode::F2), repeat: false, .. } => {
+                        let persistence = self.display.persistence().next(self.fade_frames);
+                        self.osd.message(format!("persistence: {:?}", persistence));
+                        self.display.set_persistence(persistence);
+                    }
+                    Event::KeyDown { keycode: Some(Keycode::F3), repeat: false, .. } => {
+                        self.sound.set_muted(!self.sound.is_muted());
+                        self.osd.message(if self.sound.is_muted() { "muted" } else { "unmuted" });
+                    }
+                    Event::KeyDown { keycode: Some(Keycode::Minus), .. } => {
+                        self.sound.set_volume(self.sound.volume() - VOLUME_STEP);
+                        self.osd.message(format!("volume: {:.0}", self.sound.volume() * 100.0));
+                    }
+                    Event::KeyDown { keycode: Some(Keycode::Equals), .. } => {
+                        self.sound.set_volume(self.sound.volume() + VOLUME_STEP);
+                        self.osd.message(format!("volume: {:.0}", self.sound.volume() * 100.0));
+                    }
+                    Event::KeyDown { keycode: Some(Keycode::F4), repeat: false, .. } => {
+                        let scaling = self.display.scaling().next();
+                        self.osd.message(format!("scaling: {:?}", scaling));
+                        self.display.set_scaling(scaling);
+                    }
+                    Event::KeyDown { keycode: Some(Keycode::F11), repeat: false, .. } => self.toggle_fullscreen(),
+                    Event::KeyDown { keycode: Some(Keycode::F1), repeat: false, .. } => self.osd.toggle_counter(),
+                    Event::KeyDown { keycode: Some(Keycode::F5), repeat: false, .. } => {
+                        stepping.toggle_pause();
+                        if stepping.is_paused() {
+                            self.sound.beep(false);
+                        } else {
+                            // the time spent paused isn't a slow frame
+                            fps_meter = FpsMeter::new();
+                        }
+                        self.osd.message(if stepping.is_paused() { "paused" } else { "resumed" });
+                        self.display.set_title(&window_title(title, fps, &stepping));
+                    }
+                    // a frame or an instruction at a time, pausing first if need be
+                    Event::KeyDown { keycode: Some(keycode @ (Keycode::F6 | Keycode::F7)), .. } => {
+                        if stepping.pause() {
+                            self.sound.beep(false);
+                            self.display.set_title(&window_title(title, fps, &stepping));
+                        }
+                        if keycode == Keycode::F6 {
+                            stepping.advance();
+                        } else if cpu.is_awaiting_keypress() {
+                            self.osd.message("waiting for a key");
+                        } else {
+                            let (pc, opcode) = (cpu.pc(), cpu.fetch());
+                            if cpu.tick(self.input.keys_pressed()).vram_changed {
+                                self.display.draw(cpu.vram());
+                            }
+                            self.osd.message(format!("{:03X}: {:04X}  {}", pc, opcode, disasm::disassemble(opcode)));
+                        }
+                    }
+                    Event::KeyDown { keycode: Some(Keycode::Tab), repeat: false, .. } => {
+                        stepping.set_fast_forward(true);
+                        self.sound.beep(false);
+                        self.display.set_title(&window_title(title, fps, &stepping));
+                    }
+                    Event::KeyUp { keycode: Some(Keycode::Tab), .. } => {
+                        stepping.set_fast_forward(false);
+                        self.display.set_title(&window_title(title, fps, &stepping));
+                    }
+                    Event::KeyDown { keycode: Some(Keycode::Backquote), repeat: false, .. } => {
+                        let turbo = stepping.toggle_turbo();
+                        self.sound.beep(false);
+                        self.osd.message(if turbo { "turbo on" } else { "turbo off" });
+                        self.display.set_title(&window_title(title, fps, &stepping));
+                    }
+                    Event::KeyDown { keycode: Some(Keycode::F8), repeat: false, .. } => {
+                        let slow_motion = stepping.next_slow_motion();
+                        self.osd.message(if slow_motion == 1 {
+                            "full speed".to_string()
+                        } else {
+                            format!("slow motion: {}%", 100 / slow_motion)
+                        });
+                        self.display.set_title(&window_title(title, fps, &stepping));
+                    }
+                    Event::KeyDown { keycode: Some(Keycode::F9), repeat: false, .. } => {
+                        let palette = self.display.palette().next();
+                        self.osd.message(format!("palette: {}", palette.name));
+                        self.display.set_palette(palette);
+                    }
+                    Event::KeyDown { keycode: Some(Keycode::F12), keymod, repeat: false, .. }
+                        if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) => {
+                        match self.recorder.take() {
+                            Some(recording) => {
+                                let path = recording.path().to_path_buf();
+                                match recording.finish() {
+                                    Ok(()) => self.osd.message(format!("recorded {}", path.display())),
+                                    Err(e) => self.osd.message(e),
+                                }
+                            }
+                            None => {
+                                let path = capture::timestamped_path("chippy8", "gif");
+                                match Recorder::create(&path, &self.capture_palette(), self.capture_scale) {
+                                    Ok(recording) => {
+                                        self.osd.message(format!("recording to {}", path.display()));
+                                        self.recorder = Some(recording);
+                                    }
+                                    Err(e) => self.osd.message(e),
+                                }
+                            }
+                        }
+                    }
+                    Event::KeyDown { keycode: Some(Keycode::F12), repeat: false, .. } => {
+                        let path = capture::timestamped_path("chippy8", "png");
+                        match capture::save_png(&path, cpu.vram(), &self.capture_palette(), self.capture_scale) {
+                            Ok(()) => self.osd.message(format!("saved {}", path.display())),
+                            Err(e) => self.osd.message(e),
+                        }
+                    }
+                    Event::KeyDown { keycode: Some(Keycode::Home), repeat: false, .. } => {
+                        let pacing = if pacer.pacing() == Pacing::Uncapped { capped_pacing } else { Pacing::Uncapped };
+                        self.osd.message(format!("pacing: {:?}", pacing));
+                        pacer.set_pacing(pacing);
+                    }
+                    // ctrl and a digit saves to that slot, shift and a digit loads it
+                    Event::KeyDown { keycode: Some(keycode), keymod, repeat: false, .. }
+                        if state_slot(keycode).is_some() &&
+                            keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD | Mod::LSHIFTMOD | Mod::RSHIFTMOD) => {
+                        let slot = state_slot(keycode).unwrap_or(0);
+                        let result = if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) {
+                            states.save(slot, &cpu).map(|_| format!("state {} saved", slot))
+                        } else {
+                            states.load(slot, &mut cpu).map(|_| format!("state {} loaded", slot))
+                        };
+                        self.osd.message(result.unwrap_or_else(|e| e));
+                        self.display.draw(cpu.vram());
+                    }
+                    Event::KeyDown {..} => { self.input.keydown(event) }
+                    Event::KeyUp {..} => { self.input.keyup(event) }
+                    Event::ControllerButtonDown {..} |
+                    Event::ControllerButtonUp {..} |
+                    Event::ControllerAxisMotion {..} => { self.input.controller_event(event) }
+                    Event::Window { win_event: WindowEvent::SizeChanged(..), .. } |
+                    Event::Window { win_event: WindowEvent::Exposed, .. } => { self.display.redraw() }
+                    _ => {}
+                }
+            }
+            let frames = stepping.frames();
+            let keys = self.input.keys_pressed();
+            let mut vram_changed = false;
+            let mut ran = 0;
+            while ran < frames {
+                let output = cpu.tick_60_hz(keys);
+                vram_changed |= output.vram_changed;
+                if stepping.beeps() {
+                    self.sound.beep(output.beep);
+                }
+                if let Some(wav) = &mut self.wav {
+                    wav.write_frame(output.beep)?;
+                }
+                if let Some(recording) = &mut self.recorder {
+                    if let Err(e) = recording.write_frame(output.vram) {
+                        self.osd.message(format!("{}, recording stopped", e));
+                        self.recorder = None;
+                    }
+                }
+                self.frames += 1;
+                if options.frames == Some(self.frames) {
+                    break 'game_loop Exit::Quit;
+                }
+                ran += 1;
+                if stepping::out_of_time(frames, frame_start.elapsed()) {
+                    break;
+                }
+            }
+            if ran > 0 {
+                // only the last of the frames is shown
+                if vram_changed || !self.display.is_settled() {
+                    self.display.draw(cpu.vram());
+                }
+                // a reload or a loaded state can take the count back
+                let instructions = cpu.instruction_count().saturating_sub(instruction_count);
+                instruction_count = cpu.instruction_count();
+                if let Some(rates) = fps_meter.tick(ran, instructions, frame_start.elapsed()) {
+                    fps = Some(rates.fps);
+                    self.display.set_title(&window_title(title, fps, &stepping));
+                    self.osd.set_counter(format!("{:.1} fps  {:.0} ips  {:.2} ms",
+                                                 rates.fps, rates.instructions_per_second,
+                                                 rates.frame_time.as_secs_f64() * 1000.0));
+                }
+            }
+            self.display.set_osd(self.osd.lines());
+            pacer.wait(&self.sound);
+        };
+        if let (Exit::Quit, Some(path)) = (&exit, &options.screenshot) {
+            capture::save_png(path, cpu.vram(), &self.capture_palette(), self.capture_scale)?;
+        }
+        Ok(exit)
+    }
+
+    // closes the captures and remembers where the window was
+    fn finish(mut self) -> Result<(), String> {
+        if !self.display.is_fullscreen() {
+            self.window_geometry = self.display.window_geometry();
+        }
+        let ((width, height), (x, y)) = self.window_geometry;
+        let window = WindowConfig { width: Some(width), height: Some(height), x: Some(x), y: Some(y) };
+        if let Err(e) = self.config.save_window(window) {
+            println!("unable to save window geometry: {}", e);
+        }
+        if let Some(recording) = self.recorder {
+            recording.finish()?;
+        }
+        if let Some(wav) = self.wav {
+            wav.finish()?;
         }
+        Ok(())
     }
 }
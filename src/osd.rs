@@ -0,0 +1,52 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+// how long a message stays up
+const MESSAGE_TIME: Duration = Duration::from_secs(2);
+// older messages are dropped early past this many
+const MAX_MESSAGES: usize = 4;
+
+// what's drawn over the game: status messages that fade after a couple of
+// seconds, and the performance counter while it's switched on
+pub struct Osd {
+    messages: VecDeque<(String, Instant)>,
+    show_counter: bool,
+    counter: String,
+}
+
+impl Osd {
+    pub fn new() -> Self {
+        Osd { messages: VecDeque::new(), show_counter: false, counter: String::new() }
+    }
+
+    // also printed, for a terminal or a log
+    pub fn message(&mut self, text: impl Into<String>) {
+        let text = text.into();
+        println!("{}", text);
+        self.show(text);
+    }
+
+    // for what's been printed already
+    pub fn show(&mut self, text: String) {
+        if self.messages.len() == MAX_MESSAGES {
+            self.messages.pop_front();
+        }
+        self.messages.push_back((text, Instant::now()));
+    }
+
+    pub fn toggle_counter(&mut self) {
+        self.show_counter = !self.show_counter;
+    }
+
+    pub fn set_counter(&mut self, text: String) {
+        self.counter = text;
+    }
+
+    // the counter, if it's on, and then the messages still up, oldest first
+    pub fn lines(&mut self) -> Vec<String> {
+        let now = Instant::now();
+        self.messages.retain(|(_, shown)| now - *shown < MESSAGE_TIME);
+        let counter = Some(&self.counter).filter(|counter| self.show_counter && !counter.is_empty());
+        counter.into_iter().chain(self.messages.iter().map(|(text, _)| text)).cloned().collect()
+    }
+}
@@ -0,0 +1,187 @@
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::sound::Sound;
+
+const FRAMES_PER_SECOND: u64 = 60;
+
+// falling further behind than this (the window being dragged, the host
+// stalling) starts the clock over instead of racing to catch up
+const MAX_LAG_FRAMES: u64 = 6;
+
+// how long to doze between looks at the audio clock
+const AUDIO_POLL: Duration = Duration::from_millis(1);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pacing {
+    // 60 frames a second by the host's clock
+    WallClock,
+    // one frame per 1/60 second of samples the audio device has played,
+    // so emulation can't drift away from the sound
+    Audio,
+    // frames back to back as fast as the host allows
+    Uncapped,
+}
+
+impl Pacing {
+    // "wall", "audio" or "uncapped"
+    pub fn from_name(name: &str) -> Option<Pacing> {
+        match name {
+            "wall" => Some(Pacing::WallClock),
+            "audio" => Some(Pacing::Audio),
+            "uncapped" => Some(Pacing::Uncapped),
+            _ => None,
+        }
+    }
+}
+
+// keeps frames on schedule against a deadline computed from the frame count
+// since the clock started, so rounding never builds up into drift
+pub struct Pacer {
+    pacing: Pacing,
+    start: Instant,
+    start_sample: Option<u64>,
+    frames: u64,
+}
+
+fn frame_time(frames: u64) -> Duration {
+    Duration::from_nanos(frames * 1_000_000_000 / FRAMES_PER_SECOND)
+}
+
+impl Pacer {
+    pub fn new(pacing: Pacing) -> Self {
+        Pacer {
+            pacing,
+            start: Instant::now(),
+            start_sample: None,
+            frames: 0,
+        }
+    }
+
+    pub fn pacing(&self) -> Pacing {
+        self.pacing
+    }
+
+    pub fn set_pacing(&mut self, pacing: Pacing) {
+        self.pacing = pacing;
+        self.restart(Instant::now());
+    }
+
+    fn restart(&mut self, now: Instant) {
+        self.start = now;
+        self.start_sample = None;
+        self.frames = 0;
+    }
+
+    // called after every frame, returns when the next one is due. audio
+    // pacing falls back to the wall clock when there's no audio device.
+    pub fn wait(&mut self, sound: &Sound) {
+        self.frames += 1;
+        match (self.pacing, sound.clock()) {
+            (Pacing::Uncapped, _) => {}
+            (Pacing::Audio, Some(_)) => self.wait_for_audio(sound),
+            (Pacing::Audio, None) | (Pacing::WallClock, _) => self.wait_for_wall_clock(),
+        }
+    }
+
+    fn wait_for_wall_clock(&mut self) {
+        if let Some(delay) = self.wall_clock_delay(Instant::now()) {
+            thread::sleep(delay);
+        }
+    }
+
+    // how long until the frame is due, or None once it is
+    fn wall_clock_delay(&mut self, now: Instant) -> Option<Duration> {
+        let due = self.start + frame_time(self.frames);
+        if now < due {
+            return Some(due - now);
+        }
+        if now - due > frame_time(MAX_LAG_FRAMES) {
+            self.restart(now);
+        }
+        None
+    }
+
+    fn wait_for_audio(&mut self, sound: &Sound) {
+        while let Some((samples, sample_rate)) = sound.clock() {
+            if self.audio_due(samples, sample_rate) {
+                return;
+            }
+            thread::sleep(AUDIO_POLL);
+        }
+    }
+
+    // whether the device has played as far as the frame, the first look
+    // at the clock after a start setting where it counts from
+    fn audio_due(&mut self, samples: u64, sample_rate: i32) -> bool {
+        let samples_per_frame = sample_rate as u64 / FRAMES_PER_SECOND;
+        let start_sample = *self.start_sample.get_or_insert(samples);
+        let due = start_sample + self.frames * samples_per_frame;
+        if samples < due {
+            return false;
+        }
+        if samples - due > MAX_LAG_FRAMES * samples_per_frame {
+            self.restart(Instant::now());
+        }
+        true
+    }
+}
+
+// what FpsMeter measured over the last second
+pub struct Rates {
+    // emulated frames, which fast-forward runs several of per host frame
+    pub fps: f64,
+    pub instructions_per_second: f64,
+    // the average time a host frame took to emulate and draw, leaving out
+    // the wait for the next one
+    pub frame_time: Duration,
+}
+
+// frames and instructions per second as actually achieved, measured over a
+// second at a time
+pub struct FpsMeter {
+    since: Instant,
+    host_frames: u32,
+    frames: u32,
+    instructions: u64,
+    busy: Duration,
+}
+
+impl FpsMeter {
+    pub fn new() -> Self {
+        FpsMeter::starting(Instant::now())
+    }
+
+    fn starting(since: Instant) -> Self {
+        FpsMeter { since, host_frames: 0, frames: 0, instructions: 0, busy: Duration::ZERO }
+    }
+
+    // counts a host frame that ran frames and instructions and took busy
+    // to emulate and draw, and once a second returns the rates since the
+    // last time
+    pub fn tick(&mut self, frames: u32, instructions: u64, busy: Duration) -> Option<Rates> {
+        self.tick_at(Instant::now(), frames, instructions, busy)
+    }
+
+    fn tick_at(&mut self, now: Instant, frames: u32, instructions: u64, busy: Duration) -> Option<Rates> {
+        self.host_frames += 1;
+        self.frames += frames;
+        self.instructions += instructions;
+        self.busy += busy;
+        let elapsed = now - self.since;
+        if elapsed < Duration::from_secs(1) {
+            return None;
+        }
+        let seconds = elapsed.as_secs_f64();
+        let rates = Rates {
+            fps: self.frames as f64 / seconds,
+            instructions_per_second: self.instructions as f64 / seconds,
+            frame_time: self.busy / self.host_frames,
+        };
+        *self = FpsMeter::starting(now);
+        Some(rates)
+    }
+}
+
+#[cfg(test)]
+#[path = "./pacer_tests.rs"]
+mod pacer_tests;
@@ -0,0 +1,91 @@
+use super::*;
+
+const MILLISECOND: Duration = Duration::from_millis(1);
+
+// a pacer that has just run its first frame
+fn pacer(start: Instant) -> Pacer {
+    Pacer { pacing: Pacing::WallClock, start, start_sample: None, frames: 1 }
+}
+
+
+#[test]
+fn test_frame_time() {
+    assert_eq!(frame_time(1), Duration::from_nanos(16_666_666));
+    assert_eq!(frame_time(3), Duration::from_millis(50));
+    assert_eq!(frame_time(60), Duration::from_secs(1));
+}
+
+
+#[test]
+fn test_wall_clock() {
+    let start = Instant::now();
+    let mut pacer = pacer(start);
+    assert_eq!(pacer.wall_clock_delay(start), Some(frame_time(1)));
+    assert_eq!(pacer.wall_clock_delay(start + 10 * MILLISECOND), Some(frame_time(1) - 10 * MILLISECOND));
+    assert_eq!(pacer.wall_clock_delay(start + frame_time(1)), None);
+    // deadlines come from the frame count, so a late frame doesn't push
+    // the ones after it back
+    for frame in 2..=60 {
+        pacer.frames = frame;
+        assert_eq!(pacer.wall_clock_delay(start + frame_time(frame - 1) + 5 * MILLISECOND),
+                   Some(frame_time(frame) - frame_time(frame - 1) - 5 * MILLISECOND));
+    }
+    assert_eq!(pacer.start + frame_time(pacer.frames), start + Duration::from_secs(1));
+}
+
+
+#[test]
+fn test_wall_clock_lag() {
+    let start = Instant::now();
+    let mut pacer = pacer(start);
+    // up to MAX_LAG_FRAMES behind it catches up
+    let late = start + frame_time(1 + MAX_LAG_FRAMES);
+    assert_eq!(pacer.wall_clock_delay(late), None);
+    assert_eq!((pacer.start, pacer.frames), (start, 1));
+    // further behind it starts over from now
+    let later = late + Duration::from_nanos(1);
+    assert_eq!(pacer.wall_clock_delay(later), None);
+    assert_eq!((pacer.start, pacer.frames), (later, 0));
+    pacer.frames = 1;
+    assert_eq!(pacer.wall_clock_delay(later), Some(frame_time(1)));
+}
+
+
+#[test]
+fn test_audio() {
+    let mut pacer = pacer(Instant::now());
+    // 735 samples a frame at 44100 Hz, counted from the first look
+    assert!(!pacer.audio_due(1000, 44100));
+    assert_eq!(pacer.start_sample, Some(1000));
+    assert!(!pacer.audio_due(1734, 44100));
+    assert!(pacer.audio_due(1735, 44100));
+    pacer.frames = 2;
+    assert!(!pacer.audio_due(2000, 44100));
+    assert!(pacer.audio_due(1000 + 2 * 735 + MAX_LAG_FRAMES * 735, 44100));
+    assert_eq!((pacer.start_sample, pacer.frames), (Some(1000), 2));
+    // too far behind, the count starts over at the next look
+    assert!(pacer.audio_due(1000 + 2 * 735 + MAX_LAG_FRAMES * 735 + 1, 44100));
+    assert_eq!((pacer.start_sample, pacer.frames), (None, 0));
+    pacer.frames = 1;
+    assert!(!pacer.audio_due(9000, 44100));
+    assert!(pacer.audio_due(9735, 44100));
+}
+
+
+#[test]
+fn test_fps_meter() {
+    let start = Instant::now();
+    let mut meter = FpsMeter::starting(start);
+    assert!(meter.tick_at(start + 400 * MILLISECOND, 1, 10, 2 * MILLISECOND).is_none());
+    assert!(meter.tick_at(start + 800 * MILLISECOND, 1, 10, 4 * MILLISECOND).is_none());
+    // a fast-forwarded host frame, once the second is up
+    let rates = meter.tick_at(start + 1250 * MILLISECOND, 8, 80, 6 * MILLISECOND).unwrap();
+    assert_eq!(rates.fps, 10.0 / 1.25);
+    assert_eq!(rates.instructions_per_second, 100.0 / 1.25);
+    assert_eq!(rates.frame_time, 4 * MILLISECOND);
+    // and counting starts again from there
+    assert!(meter.tick_at(start + 2000 * MILLISECOND, 60, 600, MILLISECOND).is_none());
+    let rates = meter.tick_at(start + 2250 * MILLISECOND, 0, 0, MILLISECOND).unwrap();
+    assert_eq!(rates.fps, 60.0);
+    assert_eq!(rates.frame_time, MILLISECOND);
+}
@@ -0,0 +1,82 @@
+use sdl2::pixels::Color;
+
+// colours by pixel value: 0 is the background, 1 the first plane, and 2
+// and 3 the second plane and both planes for multi-plane (XO-CHIP) modes
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    pub name: String,
+    pub colors: [Color; 4],
+}
+
+const fn rgb(rgb: u32) -> Color {
+    Color { r: (rgb >> 16) as u8, g: (rgb >> 8) as u8, b: rgb as u8, a: 0xff }
+}
+
+// the order the palette hotkey cycles through
+const PALETTES: [(&str, [Color; 4]); 6] = [
+    ("amber", [rgb(0x000000), rgb(0xffbf00), rgb(0xff6600), rgb(0xffffff)]),
+    ("green", [rgb(0x000000), rgb(0x33ff33), rgb(0x138013), rgb(0xccffcc)]),
+    ("lcd", [rgb(0x9bbc0f), rgb(0x0f380f), rgb(0x306230), rgb(0x8bac0f)]),
+    ("octo", [rgb(0x996600), rgb(0xffcc00), rgb(0xff6600), rgb(0x662200)]),
+    ("high-contrast", [rgb(0x000000), rgb(0xffffff), rgb(0xffff00), rgb(0x00ffff)]),
+    // Okabe-Ito colours, distinguishable with the common colour vision deficiencies
+    ("colorblind", [rgb(0x000000), rgb(0x56b4e9), rgb(0xe69f00), rgb(0xf0e442)]),
+];
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::named("amber").unwrap()
+    }
+}
+
+impl Palette {
+    pub fn named(name: &str) -> Option<Palette> {
+        PALETTES.iter()
+            .find(|(palette_name, _)| *palette_name == name)
+            .map(|&(name, colors)| Palette { name: name.to_string(), colors })
+    }
+
+    pub fn names() -> impl Iterator<Item = &'static str> {
+        PALETTES.iter().map(|&(name, _)| name)
+    }
+
+    // the named palette after this one, wrapping around. custom palettes
+    // go back to the first.
+    pub fn next(&self) -> Palette {
+        let index = PALETTES.iter()
+            .position(|(name, _)| *name == self.name)
+            .map_or(0, |index| (index + 1) % PALETTES.len());
+        let (name, colors) = PALETTES[index];
+        Palette { name: name.to_string(), colors }
+    }
+
+    // replaces colours from the start, as many as are given. invalid hex
+    // colours are reported and skipped.
+    pub fn with_hex_colors(mut self, hex_colors: &[String]) -> Palette {
+        if hex_colors.is_empty() {
+            return self;
+        }
+        for (color, hex) in self.colors.iter_mut().zip(hex_colors) {
+            match parse_hex_color(hex) {
+                Some(parsed) => *color = parsed,
+                None => println!("ignoring invalid colour '{}'", hex),
+            }
+        }
+        self.name = "custom".to_string();
+        self
+    }
+}
+
+// "#rrggbb" or "rrggbb"
+pub fn parse_hex_color(hex: &str) -> Option<Color> {
+    let hex = hex.strip_prefix('#').unwrap_or(hex);
+    // from_str_radix would take a sign too
+    if hex.len() != 6 || !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+    u32::from_str_radix(hex, 16).ok().map(rgb)
+}
+
+#[cfg(test)]
+#[path = "./palette_tests.rs"]
+mod palette_tests;
@@ -0,0 +1,51 @@
+use super::*;
+
+
+#[test]
+fn test_parse_hex_color() {
+    let cases: &[(&str, Option<u32>)] = &[
+        ("#ffbf00", Some(0xffbf00)),
+        ("ffbf00", Some(0xffbf00)),
+        ("#FFbf0a", Some(0xffbf0a)),
+        ("000000", Some(0x000000)),
+        ("#fff", None),
+        ("#ffbf000", None),
+        ("", None),
+        ("#", None),
+        ("##ffbf00", None),
+        ("#ffbg00", None),
+        ("+fffff", None),
+        ("#ff bf0", None),
+        ("#ffbf0é", None),
+    ];
+    for &(hex, expected) in cases {
+        assert_eq!(parse_hex_color(hex), expected.map(rgb), "{}", hex);
+    }
+}
+
+
+#[test]
+fn test_with_hex_colors() {
+    let amber = Palette::named("amber").unwrap();
+    assert_eq!(amber.clone().with_hex_colors(&[]), amber);
+    let colors: Vec<String> = ["#112233", "nonsense", "445566"].iter().map(|c| c.to_string()).collect();
+    let custom = amber.clone().with_hex_colors(&colors);
+    assert_eq!(custom.name, "custom");
+    assert_eq!(custom.colors, [rgb(0x112233), amber.colors[1], rgb(0x445566), amber.colors[3]]);
+    // more than four colours, the rest are ignored
+    let colors: Vec<String> = (0..6).map(|n| format!("{:06x}", n)).collect();
+    assert_eq!(amber.with_hex_colors(&colors).colors, [rgb(0), rgb(1), rgb(2), rgb(3)]);
+}
+
+
+#[test]
+fn test_next() {
+    let names: Vec<&str> = Palette::names().collect();
+    let mut palette = Palette::default();
+    for name in names.iter().skip(1).chain(names.iter().take(1)) {
+        palette = palette.next();
+        assert_eq!(palette.name, *name);
+    }
+    let custom = Palette::default().with_hex_colors(&["#123456".to_string()]);
+    assert_eq!(custom.next().name, names[0]);
+}
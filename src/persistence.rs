@@ -0,0 +1,107 @@
+// smooths over the flicker of sprites being XOR-erased and redrawn by
+// letting lit pixels linger, like the phosphor of an old CRT
+use chippy8::cpu::DISPLAY_WIDTH;
+use chippy8::cpu::DISPLAY_HEIGHT;
+
+pub const DEFAULT_FADE_FRAMES: u32 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Persistence {
+    Off,
+    // lit pixels fade out over this many frames after switching off
+    Fade(u32),
+    // a pixel is lit if it was lit in either of the last two frames
+    MaxOfTwo,
+}
+
+impl Persistence {
+    // "off", "fade" or "max2"
+    pub fn from_name(name: &str, fade_frames: u32) -> Option<Persistence> {
+        match name {
+            "off" => Some(Persistence::Off),
+            "fade" => Some(Persistence::Fade(fade_frames.max(1))),
+            "max2" => Some(Persistence::MaxOfTwo),
+            _ => None,
+        }
+    }
+
+    // for the hotkey: off -> fade -> max2 -> off
+    pub fn next(self, fade_frames: u32) -> Persistence {
+        match self {
+            Persistence::Off => Persistence::Fade(fade_frames.max(1)),
+            Persistence::Fade(_) => Persistence::MaxOfTwo,
+            Persistence::MaxOfTwo => Persistence::Off,
+        }
+    }
+}
+
+pub struct PersistenceFilter {
+    mode: Persistence,
+    previous: [[u8; DISPLAY_WIDTH]; DISPLAY_HEIGHT],
+    // what's on screen: the pixel value (palette index) each pixel last
+    // had while lit, and how bright it still is, from 0.0 to 1.0
+    value: [[u8; DISPLAY_WIDTH]; DISPLAY_HEIGHT],
+    intensity: [[f32; DISPLAY_WIDTH]; DISPLAY_HEIGHT],
+}
+
+impl PersistenceFilter {
+    pub fn new(mode: Persistence) -> Self {
+        PersistenceFilter {
+            mode,
+            previous: [[0; DISPLAY_WIDTH]; DISPLAY_HEIGHT],
+            value: [[0; DISPLAY_WIDTH]; DISPLAY_HEIGHT],
+            intensity: [[0.0; DISPLAY_WIDTH]; DISPLAY_HEIGHT],
+        }
+    }
+
+    pub fn mode(&self) -> Persistence {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: Persistence) {
+        self.mode = mode;
+    }
+
+    // feed one emulated frame
+    pub fn update(&mut self, vram: &[[u8; DISPLAY_WIDTH]; DISPLAY_HEIGHT]) {
+        for (row, vram_row) in vram.iter().enumerate() {
+            for (column, &pixel) in vram_row.iter().enumerate() {
+                let previous = self.previous[row][column];
+                let (value, intensity) = match self.mode {
+                    _ if pixel != 0 => (pixel, 1.0),
+                    Persistence::Off => (0, 0.0),
+                    Persistence::Fade(frames) => {
+                        let faded = self.intensity[row][column] - 1.0 / frames as f32;
+                        (self.value[row][column], faded.max(0.0))
+                    }
+                    Persistence::MaxOfTwo => (previous, if previous != 0 { 1.0 } else { 0.0 }),
+                };
+                self.value[row][column] = value;
+                self.intensity[row][column] = intensity;
+            }
+        }
+        self.previous = *vram;
+    }
+
+    // whether feeding the same frame again would change what's on screen
+    pub fn is_settled(&self) -> bool {
+        match self.mode {
+            Persistence::Off => true,
+            Persistence::Fade(_) => self.previous.iter().flatten()
+                .zip(self.intensity.iter().flatten())
+                .all(|(&pixel, &intensity)| pixel != 0 || intensity == 0.0),
+            Persistence::MaxOfTwo => self.previous.iter().flatten()
+                .zip(self.value.iter().flatten())
+                .all(|(&pixel, &value)| pixel == value),
+        }
+    }
+
+    // (palette index, brightness) of a pixel
+    pub fn pixel(&self, row: usize, column: usize) -> (u8, f32) {
+        (self.value[row][column], self.intensity[row][column])
+    }
+}
+
+#[cfg(test)]
+#[path = "./persistence_tests.rs"]
+mod persistence_tests;
@@ -0,0 +1,73 @@
+use super::*;
+
+type Frame = [[u8; DISPLAY_WIDTH]; DISPLAY_HEIGHT];
+
+// a frame with only the top left pixel set to value
+fn frame(value: u8) -> Frame {
+    let mut frame = [[0; DISPLAY_WIDTH]; DISPLAY_HEIGHT];
+    frame[0][0] = value;
+    frame
+}
+
+
+#[test]
+fn test_from_name() {
+    assert_eq!(Persistence::from_name("off", 4), Some(Persistence::Off));
+    assert_eq!(Persistence::from_name("fade", 6), Some(Persistence::Fade(6)));
+    // fading over no frames would divide by zero
+    assert_eq!(Persistence::from_name("fade", 0), Some(Persistence::Fade(1)));
+    assert_eq!(Persistence::from_name("max2", 4), Some(Persistence::MaxOfTwo));
+    assert_eq!(Persistence::from_name("bogus", 4), None);
+    assert_eq!(Persistence::Off.next(3), Persistence::Fade(3));
+    assert_eq!(Persistence::Fade(3).next(3), Persistence::MaxOfTwo);
+    assert_eq!(Persistence::MaxOfTwo.next(3), Persistence::Off);
+}
+
+
+#[test]
+fn test_off() {
+    let mut filter = PersistenceFilter::new(Persistence::Off);
+    filter.update(&frame(1));
+    assert_eq!(filter.pixel(0, 0), (1, 1.0));
+    filter.update(&frame(0));
+    assert_eq!(filter.pixel(0, 0), (0, 0.0));
+    assert!(filter.is_settled());
+}
+
+
+#[test]
+fn test_fade() {
+    let mut filter = PersistenceFilter::new(Persistence::Fade(4));
+    filter.update(&frame(2));
+    assert_eq!(filter.pixel(0, 0), (2, 1.0));
+    assert!(filter.is_settled());
+    // a quarter dimmer each frame once it's off, keeping its colour
+    for intensity in [0.75, 0.5, 0.25] {
+        filter.update(&frame(0));
+        assert_eq!(filter.pixel(0, 0), (2, intensity));
+        assert!(!filter.is_settled());
+    }
+    filter.update(&frame(0));
+    assert_eq!(filter.pixel(0, 0).1, 0.0);
+    assert!(filter.is_settled());
+    filter.update(&frame(0));
+    assert_eq!(filter.pixel(0, 0).1, 0.0);
+    // lighting up again is immediate
+    filter.update(&frame(1));
+    assert_eq!(filter.pixel(0, 0), (1, 1.0));
+}
+
+
+#[test]
+fn test_max_of_two() {
+    let mut filter = PersistenceFilter::new(Persistence::MaxOfTwo);
+    filter.update(&frame(1));
+    assert_eq!(filter.pixel(0, 0), (1, 1.0));
+    // erased for one frame, as a sprite being moved is, it stays lit
+    filter.update(&frame(0));
+    assert_eq!(filter.pixel(0, 0), (1, 1.0));
+    assert!(!filter.is_settled());
+    filter.update(&frame(0));
+    assert_eq!(filter.pixel(0, 0), (0, 0.0));
+    assert!(filter.is_settled());
+}
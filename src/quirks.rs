@@ -0,0 +1,91 @@
+// behaviours that differ between CHIP-8 interpreters. names follow the
+// chip-8-database platforms.json schema.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quirks {
+    // 8xy6/8xyE shift Vx in place instead of shifting Vy into Vx
+    pub shift: bool,
+    // Fx55/Fx65 advance I by x instead of x + 1
+    pub memory_increment_by_x: bool,
+    // Fx55/Fx65 leave I unchanged
+    pub memory_leave_i_unchanged: bool,
+    // sprites wrap around the screen edges instead of being clipped
+    pub wrap: bool,
+    // Bnnn jumps to nnn + Vx (x being the high nibble of nnn) instead of nnn + V0
+    pub jump: bool,
+    // DRW waits for the 60 Hz interrupt, so at most one sprite per frame
+    pub vblank: bool,
+    // 8xy1/8xy2/8xy3 reset VF
+    pub logic: bool,
+}
+
+const fn quirks(shift: bool, memory_increment_by_x: bool, memory_leave_i_unchanged: bool,
+                wrap: bool, jump: bool, vblank: bool, logic: bool) -> Quirks {
+    Quirks { shift, memory_increment_by_x, memory_leave_i_unchanged, wrap, jump, vblank, logic }
+}
+
+// what chippy8 has always done, following Cowgod's technical reference
+pub const COWGOD: Quirks = quirks(true, false, true, true, false, false, false);
+pub const ORIGINAL_CHIP8: Quirks = quirks(false, false, false, false, false, true, true);
+pub const MODERN_CHIP8: Quirks = quirks(false, false, false, false, false, false, false);
+pub const CHIP48: Quirks = quirks(true, true, false, false, true, false, false);
+pub const SUPERCHIP: Quirks = quirks(true, false, true, false, true, false, false);
+pub const XOCHIP: Quirks = quirks(false, false, false, true, false, false, false);
+
+impl Default for Quirks {
+    fn default() -> Self {
+        COWGOD
+    }
+}
+
+impl Quirks {
+    // a preset by short name or by chip-8-database platform id
+    pub fn preset(name: &str) -> Option<Quirks> {
+        match name {
+            "cowgod" | "default" => Some(COWGOD),
+            "chip8" | "vip" | "originalChip8" | "hybridVIP" => Some(ORIGINAL_CHIP8),
+            "modern" | "modernChip8" => Some(MODERN_CHIP8),
+            "chip48" | "superchip1" => Some(CHIP48),
+            "schip" | "superchip" => Some(SUPERCHIP),
+            "xochip" => Some(XOCHIP),
+            _ => None,
+        }
+    }
+
+    // the short name of the preset these are, or "custom"
+    pub fn name(&self) -> &'static str {
+        ["cowgod", "chip8", "modern", "chip48", "schip", "xochip"].iter()
+            .find(|&&name| Quirks::preset(name) == Some(*self))
+            .copied()
+            .unwrap_or("custom")
+    }
+
+    // a quirk by its chip-8-database name
+    pub fn get(&self, name: &str) -> Option<bool> {
+        Some(match name {
+            "shift" => self.shift,
+            "memoryIncrementByX" => self.memory_increment_by_x,
+            "memoryLeaveIUnchanged" => self.memory_leave_i_unchanged,
+            "wrap" => self.wrap,
+            "jump" => self.jump,
+            "vblank" => self.vblank,
+            "logic" => self.logic,
+            _ => return None,
+        })
+    }
+
+    // set a quirk by its chip-8-database name. returns false for unknown names
+    pub fn set(&mut self, name: &str, value: bool) -> bool {
+        let quirk = match name {
+            "shift" => &mut self.shift,
+            "memoryIncrementByX" => &mut self.memory_increment_by_x,
+            "memoryLeaveIUnchanged" => &mut self.memory_leave_i_unchanged,
+            "wrap" => &mut self.wrap,
+            "jump" => &mut self.jump,
+            "vblank" => &mut self.vblank,
+            "logic" => &mut self.logic,
+            _ => return false,
+        };
+        *quirk = value;
+        true
+    }
+}
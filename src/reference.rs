@@ -0,0 +1,180 @@
+// a second, deliberately plain CHIP-8 interpreter, written straight from
+// Cowgod's reference with the same quirks as Cpu, to run against it in
+// lockstep. it has no timing modes or speed settings, only what changes
+// the machine's state: when the two disagree, one of them has a bug.
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use crate::cpu::{DISPLAY_HEIGHT, DISPLAY_WIDTH, RAM_LENGTH, RESET_VECTOR};
+use crate::fonts::{HEX_DIGIT_ADDR_START, HEX_DIGIT_BYTE_LENGTH, HEX_DIGIT_DATA};
+use crate::lockstep::{Machine, Snapshot};
+use crate::quirks::Quirks;
+
+pub struct Reference {
+    state: Snapshot,
+    quirks: Quirks,
+    rng: StdRng,
+}
+
+impl Reference {
+    pub fn new(rom: &[u8], quirks: Quirks, seed: u64) -> Self {
+        let mut ram = [0; RAM_LENGTH];
+        let font = HEX_DIGIT_ADDR_START as usize;
+        ram[font..font + HEX_DIGIT_DATA.len()].copy_from_slice(&HEX_DIGIT_DATA);
+        let start = RESET_VECTOR as usize;
+        let length = rom.len().min(RAM_LENGTH - start);
+        ram[start..start + length].copy_from_slice(&rom[..length]);
+        Reference {
+            state: Snapshot {
+                pc: RESET_VECTOR,
+                v: [0; 16],
+                i: 0,
+                sp: 0,
+                stack: [0; 16],
+                delay_timer: 0,
+                sound_timer: 0,
+                waiting: None,
+                ram,
+                vram: [[0; DISPLAY_WIDTH]; DISPLAY_HEIGHT],
+            },
+            quirks,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    fn execute(&mut self, opcode: u16, keys: &[bool; 16]) {
+        let quirks = self.quirks;
+        let s = &mut self.state;
+        let x = (opcode as usize >> 8) & 0xF;
+        let y = (opcode as usize >> 4) & 0xF;
+        let n = opcode & 0xF;
+        let kk = opcode as u8;
+        let nnn = opcode & 0xFFF;
+        let mask = RAM_LENGTH - 1;
+        let mut next = s.pc + 2;
+        let skip = s.pc + 4;
+        match opcode >> 12 {
+            0x0 if opcode == 0x00E0 => s.vram = [[0; DISPLAY_WIDTH]; DISPLAY_HEIGHT],
+            0x0 if opcode == 0x00EE => {
+                next = s.stack[s.sp as usize];
+                s.sp = (s.sp + 15) % 16;
+            }
+            0x1 => next = nnn,
+            0x2 => {
+                s.sp = (s.sp + 1) % 16;
+                s.stack[s.sp as usize] = next & mask as u16;
+                next = nnn;
+            }
+            0x3 if s.v[x] == kk => next = skip,
+            0x4 if s.v[x] != kk => next = skip,
+            0x5 if s.v[x] == s.v[y] => next = skip,
+            0x6 => s.v[x] = kk,
+            0x7 => s.v[x] = s.v[x].wrapping_add(kk),
+            0x8 => {
+                let (vx, vy) = (s.v[x], s.v[y]);
+                let shifted = if quirks.shift { vx } else { vy };
+                let (result, flag) = match n {
+                    0x0 => (vy, None),
+                    0x1 => (vx | vy, if quirks.logic { Some(0) } else { None }),
+                    0x2 => (vx & vy, if quirks.logic { Some(0) } else { None }),
+                    0x3 => (vx ^ vy, if quirks.logic { Some(0) } else { None }),
+                    0x4 => (vx.wrapping_add(vy), Some((vx as u16 + vy as u16 > 0xFF) as u8)),
+                    0x5 => (vx.wrapping_sub(vy), Some((vx >= vy) as u8)),
+                    0x6 => (shifted >> 1, Some(shifted & 1)),
+                    0x7 => (vy.wrapping_sub(vx), Some((vy >= vx) as u8)),
+                    0xE => (shifted << 1, Some(shifted >> 7)),
+                    _ => (vx, None),
+                };
+                s.v[x] = result;
+                if let Some(flag) = flag {
+                    s.v[0xF] = flag;
+                }
+            }
+            0x9 if n == 0 && s.v[x] != s.v[y] => next = skip,
+            0xA => s.i = nnn,
+            0xB => next = nnn + s.v[if quirks.jump { x } else { 0 }] as u16,
+            0xC => s.v[x] = self.rng.gen::<u8>() & kk,
+            0xD => {
+                s.v[0xF] = 0;
+                let (left, top) = (s.v[x] as usize % DISPLAY_WIDTH, s.v[y] as usize % DISPLAY_HEIGHT);
+                for row in 0..n as usize {
+                    let sprite = s.ram[(s.i as usize + row) & mask];
+                    for column in 0..8 {
+                        let (mut px, mut py) = (left + column, top + row);
+                        if px >= DISPLAY_WIDTH || py >= DISPLAY_HEIGHT {
+                            if !quirks.wrap {
+                                continue;
+                            }
+                            px %= DISPLAY_WIDTH;
+                            py %= DISPLAY_HEIGHT;
+                        }
+                        let bit = (sprite >> (7 - column)) & 1;
+                        if bit == 1 && s.vram[py][px] == 1 {
+                            s.v[0xF] = 1;
+                        }
+                        s.vram[py][px] ^= bit;
+                    }
+                }
+            }
+            0xE if kk == 0x9E && keys[s.v[x] as usize & 0xF] => next = skip,
+            0xE if kk == 0xA1 && !keys[s.v[x] as usize & 0xF] => next = skip,
+            0xF => match kk {
+                0x07 => s.v[x] = s.delay_timer,
+                0x0A => s.waiting = Some(x as u8),
+                0x15 => s.delay_timer = s.v[x],
+                0x18 => s.sound_timer = s.v[x],
+                0x1E => s.i = (s.i + s.v[x] as u16) & mask as u16,
+                0x29 => s.i = HEX_DIGIT_ADDR_START + s.v[x] as u16 * HEX_DIGIT_BYTE_LENGTH as u16,
+                0x33 => {
+                    let i = s.i as usize;
+                    s.ram[i & mask] = s.v[x] / 100;
+                    s.ram[(i + 1) & mask] = s.v[x] / 10 % 10;
+                    s.ram[(i + 2) & mask] = s.v[x] % 10;
+                }
+                0x55 | 0x65 => {
+                    for register in 0..=x {
+                        let addr = (s.i as usize + register) & mask;
+                        if kk == 0x55 {
+                            s.ram[addr] = s.v[register];
+                        } else {
+                            s.v[register] = s.ram[addr];
+                        }
+                    }
+                    if !quirks.memory_leave_i_unchanged {
+                        let advance = if quirks.memory_increment_by_x { x } else { x + 1 };
+                        s.i = (s.i + advance as u16) & mask as u16;
+                    }
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        s.pc = next & mask as u16;
+    }
+}
+
+impl Machine for Reference {
+    // LD Vx, K stops everything until a key is down
+    fn step(&mut self, keys: &[bool; 16]) {
+        if let Some(x) = self.state.waiting {
+            match keys.iter().position(|&down| down) {
+                Some(key) => {
+                    self.state.v[x as usize] = key as u8;
+                    self.state.waiting = None;
+                }
+                None => return,
+            }
+        }
+        let pc = self.state.pc as usize;
+        let opcode = u16::from_be_bytes([self.state.ram[pc], self.state.ram[(pc + 1) % RAM_LENGTH]]);
+        self.execute(opcode, keys);
+    }
+
+    fn end_frame(&mut self) {
+        self.state.delay_timer = self.state.delay_timer.saturating_sub(1);
+        self.state.sound_timer = self.state.sound_timer.saturating_sub(1);
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        self.state.clone()
+    }
+}
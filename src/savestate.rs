@@ -0,0 +1,42 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use chippy8::cpu::Cpu;
+
+// the number of slots, picked with the digit keys
+pub const SLOTS: u32 = 10;
+
+// save state files for one rom. they're named after the rom file rather
+// than its sha1, so a state still loads after the rom is rebuilt.
+pub struct SaveStates {
+    dir: PathBuf,
+    name: String,
+}
+
+impl SaveStates {
+    pub fn new(dir: &Path, rom: &Path, entry: Option<&str>) -> Self {
+        let stem = |path: &Path| path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+        let name = match entry {
+            Some(entry) => format!("{}-{}", stem(rom), stem(Path::new(entry))),
+            None => stem(rom),
+        };
+        SaveStates { dir: dir.to_path_buf(), name }
+    }
+
+    pub fn path(&self, slot: u32) -> PathBuf {
+        self.dir.join(format!("{}.{}.state", self.name, slot))
+    }
+
+    pub fn save(&self, slot: u32, cpu: &Cpu) -> Result<PathBuf, String> {
+        let path = self.path(slot);
+        fs::create_dir_all(&self.dir).map_err(|e| format!("{}: {}", self.dir.display(), e))?;
+        fs::write(&path, cpu.save_state()).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(path)
+    }
+
+    pub fn load(&self, slot: u32, cpu: &mut Cpu) -> Result<PathBuf, String> {
+        let path = self.path(slot);
+        let state = fs::read(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        cpu.load_state(&state).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(path)
+    }
+}
@@ -0,0 +1,35 @@
+// scripted input for headless runs: "key@frame" presses a chip-8 key for
+// one frame, "key@first-last" holds it from the first frame to the last.
+// frames count from 0.
+pub struct Press {
+    pub key: usize,
+    pub first: u32,
+    pub last: u32,
+}
+
+impl Press {
+    pub fn parse(text: &str) -> Result<Press, String> {
+        let invalid = || format!("invalid input '{}', expected key@frame or key@first-last", text);
+        let (key, frames) = text.split_once('@').ok_or_else(invalid)?;
+        let key = usize::from_str_radix(key, 16).ok().filter(|&key| key < 16).ok_or_else(invalid)?;
+        let (first, last) = frames.split_once('-').unwrap_or((frames, frames));
+        let first = first.parse().map_err(|_| invalid())?;
+        let last = last.parse().map_err(|_| invalid())?;
+        Ok(Press { key, first, last })
+    }
+}
+
+pub fn parse(texts: &[String]) -> Result<Vec<Press>, String> {
+    texts.iter().map(|text| Press::parse(text)).collect()
+}
+
+// the keys held during a frame
+pub fn keys(presses: &[Press], frame: u32) -> [bool; 16] {
+    let mut keys = [false; 16];
+    for press in presses {
+        if (press.first..=press.last).contains(&frame) {
+            keys[press.key] = true;
+        }
+    }
+    keys
+}
@@ -1,73 +1,255 @@
-use sdl2;
+use std::f32::consts::PI;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 use sdl2::audio::{AudioDevice, AudioCallback, AudioSpecDesired};
 
-struct SquareWave {
-    phase_inc: f32,
+pub const DEFAULT_VOLUME: f32 = 0.25;
+pub const DEFAULT_FREQUENCY: f32 = 440.0;
+// long enough to round off the edges of the tone, short enough that the
+// shortest beeps (a sound timer of 1) still sound like beeps
+pub const DEFAULT_ATTACK: f32 = 0.002;
+pub const DEFAULT_RELEASE: f32 = 0.005;
+pub const SAMPLE_RATE: i32 = 44100;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Waveform {
+    Square,
+    Triangle,
+    Sine,
+    Noise,
+}
+
+impl Waveform {
+    pub fn from_name(name: &str) -> Option<Waveform> {
+        match name {
+            "square" => Some(Waveform::Square),
+            "triangle" => Some(Waveform::Triangle),
+            "sine" => Some(Waveform::Sine),
+            "noise" => Some(Waveform::Noise),
+            _ => None,
+        }
+    }
+}
+
+// what the beeper sounds like; attack and release are in seconds
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tone {
+    pub frequency: f32,
+    pub waveform: Waveform,
+    pub volume: f32,
+    pub attack: f32,
+    pub release: f32,
+}
+
+impl Default for Tone {
+    fn default() -> Self {
+        Tone {
+            frequency: DEFAULT_FREQUENCY,
+            waveform: Waveform::Square,
+            volume: DEFAULT_VOLUME,
+            attack: DEFAULT_ATTACK,
+            release: DEFAULT_RELEASE,
+        }
+    }
+}
+
+// generates the beeper's samples. the gate opens and closes the tone, and
+// the envelope ramps the level up and down around it so the wave never
+// jumps straight to or from silence, which is what clicks.
+pub struct Synth {
+    tone: Tone,
+    sample_rate: f32,
     phase: f32,
-    volume: f32,
+    level: f32,
+    gate: bool,
+    muted: bool,
+    noise: u32,
+    noise_sample: f32,
 }
 
-impl AudioCallback for SquareWave {
-    type Channel = f32;
+impl Synth {
+    pub fn new(tone: Tone, sample_rate: i32) -> Self {
+        Synth {
+            tone,
+            sample_rate: sample_rate as f32,
+            phase: 0.0,
+            level: 0.0,
+            gate: false,
+            muted: false,
+            noise: 0x1234_5678,
+            noise_sample: 0.0,
+        }
+    }
 
-    fn callback(&mut self, out: &mut [f32]) {
+    pub fn set_gate(&mut self, gate: bool) {
+        self.gate = gate;
+    }
+
+    pub fn set_volume(&mut self, volume: f32) {
+        self.tone.volume = volume.clamp(0.0, 1.0);
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+
+    // xorshift, re-rolled once a period so noise still follows the frequency
+    fn next_noise(&mut self) -> f32 {
+        self.noise ^= self.noise << 13;
+        self.noise ^= self.noise >> 17;
+        self.noise ^= self.noise << 5;
+        self.noise as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+
+    fn wave(&self) -> f32 {
+        let p = self.phase;
+        match self.tone.waveform {
+            Waveform::Square => if p < 0.5 { 1.0 } else { -1.0 },
+            Waveform::Triangle => 1.0 - 4.0 * (p - 0.5).abs(),
+            Waveform::Sine => (2.0 * PI * p).sin(),
+            Waveform::Noise => self.noise_sample,
+        }
+    }
+
+    // per-sample step of a linear ramp over the given seconds
+    fn ramp(&self, seconds: f32) -> f32 {
+        if seconds <= 0.0 {
+            1.0
+        } else {
+            1.0 / (seconds * self.sample_rate)
+        }
+    }
+
+    pub fn next_sample(&mut self) -> f32 {
+        if self.gate && !self.muted {
+            self.level = (self.level + self.ramp(self.tone.attack)).min(1.0);
+        } else {
+            self.level = (self.level - self.ramp(self.tone.release)).max(0.0);
+        }
+        if self.level == 0.0 {
+            // start every beep from the top of the wave
+            self.phase = 0.0;
+            return 0.0;
+        }
+        let sample = self.wave() * self.level * self.tone.volume;
+        self.phase += self.tone.frequency / self.sample_rate;
+        if self.phase >= 1.0 {
+            self.phase -= 1.0;
+            self.noise_sample = self.next_noise();
+        }
+        sample
+    }
+
+    pub fn fill(&mut self, out: &mut [f32]) {
         for x in out.iter_mut() {
-            *x = match self.phase {
-                p if (0.0..0.5).contains(&p) => self.volume,
-                _ => -self.volume,
-            };
-            self.phase = (self.phase + self.phase_inc) % 1.0;
+            *x = self.next_sample();
         }
     }
 }
 
+// the synth as SDL plays it, counting the samples the device has taken so
+// the audio clock can pace emulation
+pub struct Playback {
+    synth: Synth,
+    samples: Arc<AtomicU64>,
+}
+
+impl AudioCallback for Playback {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        self.synth.fill(out);
+        self.samples.fetch_add(out.len() as u64, Ordering::Relaxed);
+    }
+}
+
+// the device plays all the time, silence included; beeps just open and
+// close the synth's gate
 pub struct Sound {
-    device_opt: Option<AudioDevice<SquareWave>>,
+    device_opt: Option<AudioDevice<Playback>>,
+    samples: Arc<AtomicU64>,
+    sample_rate: i32,
     playing: bool,
+    muted: bool,
+    volume: f32,
 }
 
 impl Sound {
-    pub fn new(sdl: &sdl2::Sdl) -> Self {
+    pub fn new(sdl: &sdl2::Sdl, tone: Tone) -> Self {
         println!("Getting audio subsystem");
         let audio_subsystem = sdl.audio().unwrap();
         let desired_spec = AudioSpecDesired {
-            freq: Some(44100),
+            freq: Some(SAMPLE_RATE),
             channels: Some(1),
-            samples: None,
+            // small buffers keep beeps prompt and the audio clock smooth
+            samples: Some(512),
         };
 
+        let samples = Arc::new(AtomicU64::new(0));
+        let mut sample_rate = SAMPLE_RATE;
         let device_opt = match audio_subsystem.open_playback(None, &desired_spec, |spec| {
-            SquareWave {
-                phase_inc: 440.0 / spec.freq as f32,
-                phase: 0.0,
-                volume: 0.25,
-            }
+            sample_rate = spec.freq;
+            Playback { synth: Synth::new(tone, spec.freq), samples: samples.clone() }
         }) {
             Err(e) => {
                 println!("Unable to initiate audio: {}", e);
                 None
             }
             Ok(device) => {
+                device.resume();
                 Some(device)
             }
         };
         Sound {
-            device_opt: device_opt,
+            device_opt,
+            samples,
+            sample_rate,
             playing: false,
+            muted: false,
+            volume: tone.volume,
+        }
+    }
+
+    // the samples played so far, and how many of them make a second;
+    // nothing without an audio device
+    pub fn clock(&self) -> Option<(u64, i32)> {
+        self.device_opt.as_ref()
+            .map(|_| (self.samples.load(Ordering::Relaxed), self.sample_rate))
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+        if let Some(device) = &mut self.device_opt {
+            device.lock().synth.set_muted(muted);
+        }
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    // volume from 0.0 to 1.0
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume.clamp(0.0, 1.0);
+        if let Some(device) = &mut self.device_opt {
+            device.lock().synth.set_volume(self.volume);
         }
     }
 
     pub fn beep(&mut self, to_beep_or_not_to_beep: bool) {
-        match &self.device_opt {
-            Some(device) => {
-                if !self.playing && to_beep_or_not_to_beep {
-                    device.resume()
-                } else if self.playing && !to_beep_or_not_to_beep {
-                    device.pause()
-                }
-            },
-            None => {},
+        if self.playing != to_beep_or_not_to_beep {
+            if let Some(device) = &mut self.device_opt {
+                device.lock().synth.set_gate(to_beep_or_not_to_beep);
+            }
         }
         self.playing = to_beep_or_not_to_beep;
     }
 }
+
+#[cfg(test)]
+#[path = "./sound_tests.rs"]
+mod sound_tests;
@@ -0,0 +1,104 @@
+use super::*;
+
+// 8 samples a period, so the phase steps are exact
+const RATE: i32 = 8000;
+const FREQUENCY: f32 = 1000.0;
+const VOLUME: f32 = 0.5;
+
+fn synth(waveform: Waveform) -> Synth {
+    let tone = Tone { frequency: FREQUENCY, waveform, volume: VOLUME, ..Tone::default() };
+    Synth::new(tone, RATE)
+}
+
+fn samples(synth: &mut Synth, count: usize) -> Vec<f32> {
+    (0..count).map(|_| synth.next_sample()).collect()
+}
+
+
+#[test]
+fn test_envelope() {
+    let mut synth = synth(Waveform::Square);
+    assert!(samples(&mut synth, 10).iter().all(|&sample| sample == 0.0));
+    // a square wave's size is the level, so it shows the envelope
+    let attack_steps = (DEFAULT_ATTACK * RATE as f32).ceil() as usize;
+    let attack_slope = VOLUME / (DEFAULT_ATTACK * RATE as f32);
+    synth.set_gate(true);
+    let attack: Vec<f32> = samples(&mut synth, attack_steps + 10).iter().map(|sample| sample.abs()).collect();
+    assert!(attack[0] > 0.0 && attack[0] <= attack_slope + 1e-6, "{}", attack[0]);
+    for pair in attack.windows(2) {
+        assert!(pair[1] >= pair[0] && pair[1] - pair[0] <= attack_slope + 1e-6, "{:?}", pair);
+    }
+    assert_eq!(attack[attack_steps], VOLUME);
+    assert_eq!(*attack.last().unwrap(), VOLUME);
+    let release_steps = (DEFAULT_RELEASE * RATE as f32).ceil() as usize;
+    let release_slope = VOLUME / (DEFAULT_RELEASE * RATE as f32);
+    synth.set_gate(false);
+    let release: Vec<f32> = samples(&mut synth, release_steps + 10).iter().map(|sample| sample.abs()).collect();
+    assert!(VOLUME - release[0] <= release_slope + 1e-6);
+    for pair in release.windows(2) {
+        assert!(pair[1] <= pair[0] && pair[0] - pair[1] <= release_slope + 1e-6, "{:?}", pair);
+    }
+    assert!(release[release_steps..].iter().all(|&sample| sample == 0.0));
+}
+
+
+#[test]
+fn test_mute() {
+    let mut synth = synth(Waveform::Square);
+    synth.set_gate(true);
+    samples(&mut synth, 100);
+    // muting releases the tone like closing the gate does, not at once
+    synth.set_muted(true);
+    let released = samples(&mut synth, 100);
+    assert!(released[0] != 0.0);
+    assert_eq!(*released.last().unwrap(), 0.0);
+    synth.set_muted(false);
+    assert!(synth.next_sample() != 0.0);
+}
+
+
+#[test]
+fn test_waveforms() {
+    let expected: &[(Waveform, [f32; 8])] = &[
+        (Waveform::Square, [1.0, 1.0, 1.0, 1.0, -1.0, -1.0, -1.0, -1.0]),
+        (Waveform::Triangle, [-1.0, -0.5, 0.0, 0.5, 1.0, 0.5, 0.0, -0.5]),
+        (Waveform::Sine, [0.0, 0.70710677, 1.0, 0.70710677, 0.0, -0.70710677, -1.0, -0.70710677]),
+    ];
+    for &(waveform, wave) in expected {
+        let mut synth = synth(waveform);
+        synth.tone.attack = 0.0;
+        synth.set_gate(true);
+        let period = samples(&mut synth, 8);
+        for (sample, expected) in period.iter().zip(wave.iter()) {
+            assert!((sample - expected * VOLUME).abs() < 1e-5, "{:?}: {:?}", waveform, period);
+        }
+    }
+}
+
+
+#[test]
+fn test_noise() {
+    let mut synth = synth(Waveform::Noise);
+    synth.tone.attack = 0.0;
+    synth.set_gate(true);
+    let noise = samples(&mut synth, 8 * 100);
+    assert!(noise.iter().all(|sample| sample.abs() <= VOLUME));
+    // a new value once a period
+    for period in noise.chunks(8).skip(1) {
+        assert!(period.iter().all(|&sample| sample == period[0]));
+    }
+    let periods: Vec<f32> = noise.chunks(8).map(|period| period[0]).collect();
+    assert!(periods.windows(2).filter(|pair| pair[0] != pair[1]).count() > 90);
+}
+
+
+#[test]
+fn test_volume() {
+    let mut synth = synth(Waveform::Square);
+    synth.tone.attack = 0.0;
+    synth.set_gate(true);
+    synth.set_volume(2.0);
+    assert_eq!(synth.next_sample(), 1.0);
+    synth.set_volume(0.1);
+    assert_eq!(synth.next_sample(), 0.1);
+}
@@ -0,0 +1,112 @@
+use std::time::Duration;
+
+// the slow motion hotkey cycles through running one frame in each 1, 2
+// and 4 of the host's
+const SLOW_MOTION_STEPS: [u32; 3] = [1, 2, 4];
+
+// how long fast-forward runs frames for in each of the host's, leaving the
+// rest of the 1/60 s to draw and handle events
+pub const FAST_FORWARD_TIME: Duration = Duration::from_millis(12);
+
+// the frames to run when fast-forward runs as many as fit
+pub const AS_MANY_AS_FIT: u32 = u32::MAX;
+
+// how many of the game's frames to run in each of the host's. while paused
+// only the frame advance hotkey runs one, and in slow motion it runs one
+// frame in every slow_motion of the host's. fast-forward runs while tab is
+// held and turbo until ` is pressed again; both run fast_forward_frames
+// frames a host frame, or as many as fit if it's 0.
+pub struct Stepping {
+    paused: bool,
+    advance: bool,
+    slow_motion: u32,
+    host_frames: u32,
+    fast_forward: bool,
+    turbo: bool,
+    fast_forward_frames: u32,
+}
+
+impl Stepping {
+    pub fn new(fast_forward_frames: u32) -> Self {
+        Stepping {
+            paused: false,
+            advance: false,
+            slow_motion: SLOW_MOTION_STEPS[0],
+            host_frames: 0,
+            fast_forward: false,
+            turbo: false,
+            fast_forward_frames,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn slow_motion(&self) -> u32 {
+        self.slow_motion
+    }
+
+    pub fn is_fast(&self) -> bool {
+        self.fast_forward || self.turbo
+    }
+
+    // the beeper stays off at more than full speed rather than chopping up
+    pub fn beeps(&self) -> bool {
+        !self.is_fast()
+    }
+
+    pub fn set_fast_forward(&mut self, fast_forward: bool) {
+        self.fast_forward = fast_forward;
+    }
+
+    pub fn toggle_turbo(&mut self) -> bool {
+        self.turbo = !self.turbo;
+        self.turbo
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    // true if it wasn't paused already
+    pub fn pause(&mut self) -> bool {
+        !std::mem::replace(&mut self.paused, true)
+    }
+
+    // one frame on the next host frame, pausing first if need be
+    pub fn advance(&mut self) {
+        self.paused = true;
+        self.advance = true;
+    }
+
+    pub fn next_slow_motion(&mut self) -> u32 {
+        let next = SLOW_MOTION_STEPS.iter().position(|&step| step == self.slow_motion).unwrap_or(0) + 1;
+        self.slow_motion = SLOW_MOTION_STEPS[next % SLOW_MOTION_STEPS.len()];
+        self.slow_motion
+    }
+
+    // called once a host frame. only the last of the frames is drawn
+    pub fn frames(&mut self) -> u32 {
+        let frames = if self.paused {
+            std::mem::take(&mut self.advance) as u32
+        } else if self.is_fast() && self.fast_forward_frames == 0 {
+            AS_MANY_AS_FIT
+        } else if self.is_fast() {
+            self.fast_forward_frames
+        } else {
+            (self.host_frames % self.slow_motion == 0) as u32
+        };
+        self.host_frames = self.host_frames.wrapping_add(1);
+        frames
+    }
+}
+
+// whether to stop running frames, elapsed into the host frame
+pub fn out_of_time(frames: u32, elapsed: Duration) -> bool {
+    frames == AS_MANY_AS_FIT && elapsed >= FAST_FORWARD_TIME
+}
+
+#[cfg(test)]
+#[path = "./stepping_tests.rs"]
+mod stepping_tests;
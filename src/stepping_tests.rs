@@ -0,0 +1,109 @@
+use super::*;
+
+fn run(stepping: &mut Stepping, host_frames: usize) -> Vec<u32> {
+    (0..host_frames).map(|_| stepping.frames()).collect()
+}
+
+
+#[test]
+fn test_full_speed() {
+    let mut stepping = Stepping::new(0);
+    assert_eq!(run(&mut stepping, 3), vec![1, 1, 1]);
+}
+
+
+#[test]
+fn test_pause() {
+    let mut stepping = Stepping::new(0);
+    stepping.toggle_pause();
+    assert!(stepping.is_paused());
+    assert_eq!(run(&mut stepping, 3), vec![0, 0, 0]);
+    // pausing again does nothing, and resuming runs every frame again
+    assert!(!stepping.pause());
+    stepping.toggle_pause();
+    assert!(!stepping.is_paused());
+    assert_eq!(run(&mut stepping, 2), vec![1, 1]);
+    assert!(stepping.pause());
+    assert!(stepping.is_paused());
+}
+
+
+#[test]
+fn test_advance() {
+    let mut stepping = Stepping::new(0);
+    // pauses first, and runs just the one frame
+    stepping.advance();
+    assert!(stepping.is_paused());
+    assert_eq!(run(&mut stepping, 3), vec![1, 0, 0]);
+    // presses between host frames don't add up
+    stepping.advance();
+    stepping.advance();
+    assert_eq!(run(&mut stepping, 2), vec![1, 0]);
+}
+
+
+#[test]
+fn test_slow_motion() {
+    let mut stepping = Stepping::new(0);
+    assert_eq!(stepping.slow_motion(), 1);
+    assert_eq!(stepping.next_slow_motion(), 2);
+    assert_eq!(run(&mut stepping, 4), vec![1, 0, 1, 0]);
+    assert_eq!(stepping.next_slow_motion(), 4);
+    assert_eq!(run(&mut stepping, 8), vec![1, 0, 0, 0, 1, 0, 0, 0]);
+    assert_eq!(stepping.next_slow_motion(), 1);
+    assert_eq!(run(&mut stepping, 2), vec![1, 1]);
+    // a frame advance runs its frame whatever the slow motion
+    stepping.next_slow_motion();
+    stepping.advance();
+    assert_eq!(run(&mut stepping, 2), vec![1, 0]);
+}
+
+
+#[test]
+fn test_fast_forward() {
+    let mut stepping = Stepping::new(5);
+    assert!(stepping.beeps());
+    stepping.set_fast_forward(true);
+    assert!(stepping.is_fast());
+    assert!(!stepping.beeps());
+    assert_eq!(run(&mut stepping, 2), vec![5, 5]);
+    // slow motion only counts at full speed
+    stepping.next_slow_motion();
+    assert_eq!(run(&mut stepping, 2), vec![5, 5]);
+    stepping.set_fast_forward(false);
+    assert!(stepping.beeps());
+    assert_eq!(run(&mut stepping, 2), vec![1, 0]);
+    // pausing stops it, though the beeper stays off
+    stepping.set_fast_forward(true);
+    stepping.advance();
+    assert!(!stepping.beeps());
+    assert_eq!(run(&mut stepping, 2), vec![1, 0]);
+}
+
+
+#[test]
+fn test_turbo() {
+    let mut stepping = Stepping::new(0);
+    assert!(stepping.toggle_turbo());
+    assert!(!stepping.beeps());
+    assert_eq!(run(&mut stepping, 1), vec![AS_MANY_AS_FIT]);
+    // letting go of tab leaves turbo on
+    stepping.set_fast_forward(true);
+    stepping.set_fast_forward(false);
+    assert!(stepping.is_fast());
+    assert!(!stepping.toggle_turbo());
+    assert!(!stepping.is_fast());
+    assert_eq!(run(&mut stepping, 1), vec![1]);
+}
+
+
+#[test]
+fn test_out_of_time() {
+    let late = FAST_FORWARD_TIME + Duration::from_millis(1);
+    assert!(!out_of_time(AS_MANY_AS_FIT, Duration::ZERO));
+    assert!(out_of_time(AS_MANY_AS_FIT, FAST_FORWARD_TIME));
+    assert!(out_of_time(AS_MANY_AS_FIT, late));
+    // a set number of frames all run, however long they take
+    assert!(!out_of_time(5, late));
+    assert!(!out_of_time(1, late));
+}
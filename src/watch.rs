@@ -0,0 +1,57 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+// how often the rom file is looked at
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// notices a rom file being rewritten, by polling its modification time.
+// a change only counts once the time has held still for a poll, so a
+// build that's still writing the file isn't picked up half done.
+pub struct RomWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    pending: Option<SystemTime>,
+    poll_interval: Duration,
+    last_poll: Instant,
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+impl RomWatcher {
+    pub fn new(path: &Path, poll_interval: Duration) -> Self {
+        RomWatcher {
+            path: path.to_path_buf(),
+            modified: modified(path),
+            pending: None,
+            poll_interval,
+            last_poll: Instant::now(),
+        }
+    }
+
+    // called every frame; true once after each change
+    pub fn changed(&mut self) -> bool {
+        if self.last_poll.elapsed() < self.poll_interval {
+            return false;
+        }
+        self.last_poll = Instant::now();
+        let now = modified(&self.path);
+        if now.is_none() || now == self.modified {
+            self.pending = None;
+            return false;
+        }
+        if self.pending != now {
+            self.pending = now;
+            return false;
+        }
+        self.modified = now;
+        self.pending = None;
+        true
+    }
+}
+
+#[cfg(test)]
+#[path = "./watch_tests.rs"]
+mod watch_tests;
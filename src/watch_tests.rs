@@ -0,0 +1,73 @@
+use super::*;
+use std::env;
+use std::fs::File;
+use std::process;
+
+fn touch(path: &Path, seconds: u64) {
+    let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(seconds);
+    File::options().write(true).open(path).unwrap().set_modified(modified).unwrap();
+}
+
+fn rom_file(name: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("chippy8-watch-{}-{}.ch8", process::id(), name));
+    fs::write(&path, [0x12, 0x00]).unwrap();
+    touch(&path, 1_000_000);
+    path
+}
+
+
+#[test]
+fn test_settles() {
+    let path = rom_file("settles");
+    let mut watcher = RomWatcher::new(&path, Duration::ZERO);
+    assert!(!watcher.changed());
+    touch(&path, 1_000_010);
+    // seen, then the same time again a poll later
+    assert!(!watcher.changed());
+    assert!(watcher.changed());
+    assert!(!watcher.changed());
+    // still being written: each poll sees a new time
+    touch(&path, 1_000_020);
+    assert!(!watcher.changed());
+    touch(&path, 1_000_030);
+    assert!(!watcher.changed());
+    assert!(watcher.changed());
+    fs::remove_file(path).unwrap();
+}
+
+
+#[test]
+fn test_put_back() {
+    let path = rom_file("put-back");
+    let mut watcher = RomWatcher::new(&path, Duration::ZERO);
+    touch(&path, 1_000_010);
+    assert!(!watcher.changed());
+    // back as it was before the change settled
+    touch(&path, 1_000_000);
+    assert!(!watcher.changed());
+    assert!(!watcher.changed());
+    // a missing file isn't a change, and its return is
+    fs::remove_file(&path).unwrap();
+    assert!(!watcher.changed());
+    fs::write(&path, [0x12, 0x02]).unwrap();
+    touch(&path, 1_000_040);
+    assert!(!watcher.changed());
+    assert!(watcher.changed());
+    fs::remove_file(path).unwrap();
+}
+
+
+#[test]
+fn test_poll_interval() {
+    let path = rom_file("interval");
+    let mut watcher = RomWatcher::new(&path, Duration::from_secs(60));
+    touch(&path, 1_000_010);
+    assert!(!watcher.changed());
+    assert!(!watcher.changed());
+    // the next poll comes round
+    watcher.last_poll -= Duration::from_secs(60);
+    assert!(!watcher.changed());
+    watcher.last_poll -= Duration::from_secs(60);
+    assert!(watcher.changed());
+    fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,85 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use crate::sound::{Synth, Tone, SAMPLE_RATE};
+
+// 44100 / 60 is exactly 735, so every frame starts on a whole sample and
+// beeps land where they would in emulated time, however fast the host ran
+pub const SAMPLES_PER_FRAME: usize = SAMPLE_RATE as usize / 60;
+
+const HEADER_LENGTH: u32 = 44;
+
+// 16-bit mono pcm of the beeper, one frame of samples per tick_60_hz
+pub struct WavWriter {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    synth: Synth,
+    samples: u32,
+}
+
+fn header(data_length: u32) -> Vec<u8> {
+    let mut header = Vec::with_capacity(HEADER_LENGTH as usize);
+    header.extend_from_slice(b"RIFF");
+    header.extend_from_slice(&(HEADER_LENGTH - 8 + data_length).to_le_bytes());
+    header.extend_from_slice(b"WAVE");
+    header.extend_from_slice(b"fmt ");
+    header.extend_from_slice(&16u32.to_le_bytes());
+    header.extend_from_slice(&1u16.to_le_bytes()); // pcm
+    header.extend_from_slice(&1u16.to_le_bytes()); // mono
+    header.extend_from_slice(&(SAMPLE_RATE as u32).to_le_bytes());
+    header.extend_from_slice(&(SAMPLE_RATE as u32 * 2).to_le_bytes()); // bytes per second
+    header.extend_from_slice(&2u16.to_le_bytes()); // bytes per sample
+    header.extend_from_slice(&16u16.to_le_bytes()); // bits per sample
+    header.extend_from_slice(b"data");
+    header.extend_from_slice(&data_length.to_le_bytes());
+    header
+}
+
+impl WavWriter {
+    pub fn create(path: &Path, tone: Tone) -> Result<WavWriter, String> {
+        let file = File::create(path)
+            .map_err(|e| format!("cannot create {}: {}", path.display(), e))?;
+        let mut wav = WavWriter {
+            path: path.to_path_buf(),
+            writer: BufWriter::new(file),
+            synth: Synth::new(tone, SAMPLE_RATE),
+            samples: 0,
+        };
+        // the lengths are filled in by finish
+        let written = wav.writer.write_all(&header(0));
+        wav.check(written)?;
+        Ok(wav)
+    }
+
+    fn check<T>(&self, result: io::Result<T>) -> Result<T, String> {
+        result.map_err(|e| format!("cannot write {}: {}", self.path.display(), e))
+    }
+
+    // the beeper for one frame, as tick_60_hz reported it
+    pub fn write_frame(&mut self, beep: bool) -> Result<(), String> {
+        self.synth.set_gate(beep);
+        let mut samples = [0.0; SAMPLES_PER_FRAME];
+        self.synth.fill(&mut samples);
+        let mut data = Vec::with_capacity(SAMPLES_PER_FRAME * 2);
+        for sample in samples.iter() {
+            let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16;
+            data.extend_from_slice(&sample.to_le_bytes());
+        }
+        let written = self.writer.write_all(&data);
+        self.check(written)?;
+        self.samples += SAMPLES_PER_FRAME as u32;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<(), String> {
+        let data_length = self.samples * 2;
+        let written = self.writer.seek(SeekFrom::Start(0))
+            .and_then(|_| self.writer.write_all(&header(data_length)))
+            .and_then(|_| self.writer.flush());
+        self.check(written)
+    }
+}
+
+#[cfg(test)]
+#[path = "./wav_tests.rs"]
+mod wav_tests;
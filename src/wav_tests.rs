@@ -0,0 +1,46 @@
+use super::*;
+use std::env;
+use std::fs;
+use std::process;
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+}
+
+
+#[test]
+fn test_wav() {
+    let path = env::temp_dir().join(format!("chippy8-wav-{}.wav", process::id()));
+    let mut wav = WavWriter::create(&path, Tone::default()).unwrap();
+    // a second of emulated time, beeping for the middle of it
+    for frame in 0..60 {
+        wav.write_frame((20..40).contains(&frame)).unwrap();
+    }
+    wav.finish().unwrap();
+    let bytes = fs::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let data_length = SAMPLE_RATE as u32 * 2;
+    assert_eq!(bytes.len(), HEADER_LENGTH as usize + data_length as usize);
+    assert_eq!(&bytes[0..4], b"RIFF");
+    assert_eq!(u32_at(&bytes, 4), 36 + data_length);
+    assert_eq!(&bytes[8..16], b"WAVEfmt ");
+    assert_eq!(u32_at(&bytes, 16), 16);
+    assert_eq!(u16_at(&bytes, 20), 1);
+    assert_eq!(u16_at(&bytes, 22), 1);
+    assert_eq!(u32_at(&bytes, 24), SAMPLE_RATE as u32);
+    assert_eq!(u32_at(&bytes, 28), SAMPLE_RATE as u32 * 2);
+    assert_eq!(u16_at(&bytes, 32), 2);
+    assert_eq!(u16_at(&bytes, 34), 16);
+    assert_eq!(&bytes[36..40], b"data");
+    assert_eq!(u32_at(&bytes, 40), data_length);
+
+    // silent before the beep, and sounding during it
+    let sample = |frame: usize, n: usize| u16_at(&bytes, 44 + (frame * SAMPLES_PER_FRAME + n) * 2) as i16;
+    assert!((0..SAMPLES_PER_FRAME).all(|n| sample(10, n) == 0));
+    assert!((0..SAMPLES_PER_FRAME).any(|n| sample(30, n) != 0));
+}
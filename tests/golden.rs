@@ -0,0 +1,189 @@
+// golden framebuffer tests. each file in tests/golden is a case: a toml
+// header naming a rom under tests/, how long to run it and what keys to
+// press, then a "---" line and the screen expected at the end, as 32 lines
+// of 64 '.' and '#'.
+//
+// roms are either binaries, or .hex files of hex bytes and words with
+// '#' comments, for test roms written by hand.
+//
+// CHIPPY8_BLESS=1 cargo test --test golden writes the screens the cases
+// actually end on back into them, for a new case or after a change to
+// what a rom should show.
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+use chippy8::cartridge::Cartridge;
+use chippy8::cpu::{Cpu, TimingMode, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use chippy8::lockstep;
+use chippy8::quirks::Quirks;
+use chippy8::reference::Reference;
+use chippy8::script;
+
+const SEPARATOR: &str = "---";
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Case {
+    // relative to tests/
+    rom: PathBuf,
+    frames: u32,
+    // a preset name, as --quirks takes
+    quirks: Option<String>,
+    ipf: Option<u32>,
+    #[serde(default)]
+    vip: bool,
+    #[serde(default)]
+    seed: u64,
+    // "key@frame" or "key@first-last", as chippy8::script takes
+    #[serde(default)]
+    input: Vec<String>,
+}
+
+fn tests_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests")
+}
+
+fn parse_hex(text: &str) -> Result<Vec<u8>, String> {
+    let mut rom = Vec::new();
+    for line in text.lines() {
+        let code = line.split('#').next().unwrap_or_default();
+        for token in code.split_whitespace() {
+            let value = u16::from_str_radix(token, 16).map_err(|_| format!("invalid hex '{}'", token))?;
+            match token.len() {
+                2 => rom.push(value as u8),
+                4 => rom.extend_from_slice(&value.to_be_bytes()),
+                _ => return Err(format!("'{}' is neither a byte nor a word", token)),
+            }
+        }
+    }
+    Ok(rom)
+}
+
+fn load_rom(path: &Path) -> Result<Vec<u8>, String> {
+    if path.extension().is_some_and(|extension| extension == "hex") {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        return parse_hex(&text).map_err(|e| format!("{}: {}", path.display(), e));
+    }
+    Cartridge::new(path, None)
+        .map(|cart| cart.rom)
+        .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+// the screen the case ends on
+fn run(case: &Case) -> Result<Vec<String>, String> {
+    let presses = script::parse(&case.input)?;
+    let rom = load_rom(&tests_dir().join(&case.rom))?;
+    let mut cpu = Cpu::new();
+    cpu.load_rom(&rom).map_err(|e| e.to_string())?;
+    cpu.set_seed(case.seed);
+    if let Some(name) = &case.quirks {
+        cpu.set_quirks(Quirks::preset(name).ok_or_else(|| format!("unknown quirks preset '{}'", name))?);
+    }
+    if let Some(ipf) = case.ipf {
+        cpu.set_instructions_per_frame(ipf);
+    }
+    if case.vip {
+        cpu.set_timing_mode(TimingMode::CosmacVip);
+    }
+    for frame in 0..case.frames {
+        cpu.tick_60_hz(&script::keys(&presses, frame));
+    }
+    Ok(cpu.vram().iter()
+        .map(|row| row.iter().map(|&pixel| if pixel != 0 { '#' } else { '.' }).collect())
+        .collect())
+}
+
+// expected, actual, and where they differ: '+' lit but shouldn't be, '-'
+// dark but should be lit
+fn diff(expected: &[String], actual: &[String]) -> String {
+    let mut text = format!("{:<w$} | {:<w$} | diff\n", "expected", "actual", w = DISPLAY_WIDTH);
+    for row in 0..DISPLAY_HEIGHT {
+        let expected_row = expected.get(row).map(String::as_str).unwrap_or("");
+        let actual_row = &actual[row];
+        let marks: String = actual_row.chars()
+            .zip(expected_row.chars().chain(std::iter::repeat(' ')))
+            .map(|(actual, expected)| match (expected, actual) {
+                (expected, actual) if expected == actual => actual,
+                (_, '#') => '+',
+                _ => '-',
+            })
+            .collect();
+        text.push_str(&format!("{:<w$} | {} | {}\n", expected_row, actual_row, marks, w = DISPLAY_WIDTH));
+    }
+    text
+}
+
+// none when the case passes
+fn check(path: &Path, bless: bool) -> Option<String> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => return Some(e.to_string()),
+    };
+    let (header, expected) = match text.split_once(&format!("\n{}\n", SEPARATOR)) {
+        Some(parts) => parts,
+        None => return Some(format!("no '{}' line before the expected screen", SEPARATOR)),
+    };
+    let case: Case = match toml::from_str(header) {
+        Ok(case) => case,
+        Err(e) => return Some(e.to_string()),
+    };
+    let actual = match run(&case) {
+        Ok(actual) => actual,
+        Err(e) => return Some(e),
+    };
+    let expected: Vec<String> = expected.lines().map(String::from).collect();
+    if expected == actual {
+        return None;
+    }
+    if bless {
+        let blessed = format!("{}\n{}\n{}\n", header, SEPARATOR, actual.join("\n"));
+        return fs::write(path, blessed).err().map(|e| e.to_string());
+    }
+    Some(format!("screen after {} frames differs\n{}", case.frames, diff(&expected, &actual)))
+}
+
+#[test]
+fn golden_framebuffers() {
+    let bless = env::var_os("CHIPPY8_BLESS").is_some();
+    let mut cases: Vec<PathBuf> = fs::read_dir(tests_dir().join("golden"))
+        .expect("tests/golden")
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "txt"))
+        .collect();
+    cases.sort();
+    assert!(!cases.is_empty(), "no cases in tests/golden");
+    let mut failed = Vec::new();
+    for path in &cases {
+        let name = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+        if let Some(failure) = check(path, bless) {
+            println!("{}: {}", name, failure);
+            failed.push(name);
+        }
+    }
+    assert!(failed.is_empty(), "golden cases failed: {}", failed.join(", "));
+}
+
+// the hand written roms under every preset, against the reference
+// interpreter, instruction by instruction
+#[test]
+fn lockstep_with_reference() {
+    let presets = ["cowgod", "chip8", "modern", "chip48", "schip", "xochip"];
+    let mut failed = Vec::new();
+    for rom_name in &["ops.hex", "quirks.hex"] {
+        let rom = load_rom(&tests_dir().join("roms").join(rom_name)).unwrap();
+        for &name in &presets {
+            let quirks = Quirks::preset(name).unwrap();
+            let mut cpu = Cpu::new();
+            cpu.load_rom(&rom).unwrap();
+            cpu.set_seed(0);
+            cpu.set_quirks(quirks);
+            let mut reference = Reference::new(&rom, quirks, 0);
+            if let Some(divergence) = lockstep::run(&mut cpu, &mut reference, &[], 30, 1000) {
+                println!("{} under {}:\n{}", rom_name, name, divergence.report("cpu", "reference").join("\n"));
+                failed.push(format!("{} under {}", rom_name, name));
+            }
+        }
+    }
+    assert!(failed.is_empty(), "diverged from the reference: {}", failed.join(", "));
+}